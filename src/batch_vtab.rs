@@ -0,0 +1,227 @@
+use sqlite_loadable::table::{ConstraintOperator, IndexInfo, VTab, VTabArguments, VTabCursor};
+use sqlite_loadable::{api, prelude::*, BestIndexError, Error, Result};
+use std::{cell::RefCell, marker::PhantomData, mem, os::raw::c_int, rc::Rc};
+use zerocopy::AsBytes;
+
+use crate::{cache, clients::ClientRegistry, vector::VectorType};
+
+enum Columns {
+    Embedding,
+    Client,
+    Inputs,
+    InputType,
+}
+fn column(index: i32) -> Option<Columns> {
+    match index {
+        0 => Some(Columns::Embedding),
+        1 => Some(Columns::Client),
+        2 => Some(Columns::Inputs),
+        3 => Some(Columns::InputType),
+        _ => None,
+    }
+}
+
+/// Bits set on idxNum for which hidden column constraints were passed to xFilter.
+const IDX_CLIENT: i32 = 0b001;
+const IDX_INPUTS: i32 = 0b010;
+const IDX_INPUT_TYPE: i32 = 0b100;
+
+/// The `rembed_batch(client, inputs [, input_type])` table function. `inputs` is a JSON
+/// array of strings, which are sent to the client in chunks of at most
+/// `EmbeddingClient::max_batch_size()` inputs per HTTP request. One row is returned per input,
+/// in order, with `rowid` as the input's index in the array.
+#[repr(C)]
+pub struct BatchTable {
+    /// must be first
+    base: sqlite3_vtab,
+    db: *mut sqlite3,
+    clients: Rc<RefCell<ClientRegistry>>,
+}
+
+impl<'vtab> VTab<'vtab> for BatchTable {
+    type Aux = Rc<RefCell<ClientRegistry>>;
+    type Cursor = BatchCursor<'vtab>;
+
+    fn connect(
+        db: *mut sqlite3,
+        aux: Option<&Self::Aux>,
+        _args: VTabArguments,
+    ) -> Result<(String, BatchTable)> {
+        let base: sqlite3_vtab = unsafe { mem::zeroed() };
+        let clients = aux.expect("Required aux").to_owned();
+
+        let vtab = BatchTable { base, db, clients };
+        let sql =
+            "create table x(embedding, client hidden, inputs hidden, input_type hidden)".to_owned();
+
+        Ok((sql, vtab))
+    }
+
+    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
+        let mut client_idx = None;
+        let mut inputs_idx = None;
+        let mut input_type_idx = None;
+        for (i, constraint) in info.constraints().iter().enumerate() {
+            if !constraint.usable() || constraint.op() != Some(ConstraintOperator::EQ) {
+                continue;
+            }
+            match column(constraint.column_idx()) {
+                Some(Columns::Client) => client_idx = Some(i),
+                Some(Columns::Inputs) => inputs_idx = Some(i),
+                Some(Columns::InputType) => input_type_idx = Some(i),
+                _ => (),
+            }
+        }
+        let (client_idx, inputs_idx) = match (client_idx, inputs_idx) {
+            (Some(client_idx), Some(inputs_idx)) => (client_idx, inputs_idx),
+            _ => return Err(BestIndexError::Constraint),
+        };
+
+        let mut constraints = info.constraints();
+        constraints[client_idx].set_argv_index(1);
+        constraints[client_idx].set_omit(true);
+        constraints[inputs_idx].set_argv_index(2);
+        constraints[inputs_idx].set_omit(true);
+        let mut idx_num = IDX_CLIENT | IDX_INPUTS;
+        if let Some(input_type_idx) = input_type_idx {
+            constraints[input_type_idx].set_argv_index(3);
+            constraints[input_type_idx].set_omit(true);
+            idx_num |= IDX_INPUT_TYPE;
+        }
+
+        info.set_estimated_cost(100.0);
+        info.set_estimated_rows(100);
+        info.set_idxnum(idx_num);
+        Ok(())
+    }
+
+    fn open(&'vtab mut self) -> Result<BatchCursor<'vtab>> {
+        Ok(BatchCursor::new(self))
+    }
+}
+
+#[repr(C)]
+pub struct BatchCursor<'vtab> {
+    /// Base class. Must be first
+    base: sqlite3_vtab_cursor,
+    db: *mut sqlite3,
+    clients: Rc<RefCell<ClientRegistry>>,
+    embeddings: Vec<Vec<f32>>,
+    vector_type: VectorType,
+    rowid: i64,
+    phantom: PhantomData<&'vtab BatchTable>,
+}
+impl BatchCursor<'_> {
+    fn new(table: &mut BatchTable) -> BatchCursor<'_> {
+        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
+        BatchCursor {
+            base,
+            db: table.db,
+            clients: Rc::clone(&table.clients),
+            embeddings: vec![],
+            vector_type: VectorType::default(),
+            rowid: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl VTabCursor for BatchCursor<'_> {
+    fn filter(
+        &mut self,
+        idx_num: c_int,
+        _idx_str: Option<&str>,
+        values: &[*mut sqlite3_value],
+    ) -> Result<()> {
+        let client_name = api::value_text(
+            values
+                .first()
+                .ok_or_else(|| Error::new_message("client argument is required"))?,
+        )?;
+        let inputs: Vec<String> = values
+            .get(1)
+            .ok_or_else(|| Error::new_message("inputs argument is required"))
+            .and_then(|value| {
+                serde_json::from_str(api::value_text(value)?).map_err(|error| {
+                    Error::new_message(format!("inputs must be a JSON array of strings: {error}"))
+                })
+            })?;
+        let input_type = if idx_num & IDX_INPUT_TYPE != 0 {
+            values.get(2).and_then(|v| api::value_text(v).ok())
+        } else {
+            None
+        };
+
+        let clients = self.clients.borrow();
+        let registered = clients.lookup(client_name)?;
+        let input_type = input_type.or(registered.input_type.as_deref());
+        self.vector_type = registered.vector_type;
+
+        let inputs: Vec<&str> = inputs.iter().map(|input| input.as_str()).collect();
+        let stats = match &registered.cache {
+            Some(stats) => stats,
+            None => {
+                self.embeddings = registered.infer_batch(&inputs, input_type)?;
+                self.rowid = 0;
+                return Ok(());
+            }
+        };
+
+        // Only inputs missing from rembed_cache are sent to the provider
+        let cache_table = clients.cache_table();
+        let fingerprint = registered.fingerprint();
+        let keys: Vec<Vec<u8>> = inputs
+            .iter()
+            .map(|input| cache::key(&fingerprint, input_type, input))
+            .collect();
+        let mut embeddings = keys
+            .iter()
+            .map(|key| cache_table.get(self.db, key))
+            .collect::<Result<Vec<_>>>()?;
+        let missing: Vec<usize> = (0..inputs.len())
+            .filter(|i| embeddings[*i].is_none())
+            .collect();
+        stats
+            .hits
+            .set(stats.hits.get() + (inputs.len() - missing.len()) as i64);
+        stats.misses.set(stats.misses.get() + missing.len() as i64);
+
+        let missing_inputs: Vec<&str> = missing.iter().map(|i| inputs[*i]).collect();
+        let namespace = cache::namespace(&fingerprint);
+        for (i, embedding) in missing
+            .into_iter()
+            .zip(registered.infer_batch(&missing_inputs, input_type)?)
+        {
+            cache_table.put(self.db, &keys[i], &namespace, embedding.as_bytes())?;
+            embeddings[i] = Some(embedding);
+        }
+        self.embeddings = embeddings.into_iter().flatten().collect();
+        self.rowid = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.rowid += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        (self.rowid as usize) >= self.embeddings.len()
+    }
+
+    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
+        if let Some(Columns::Embedding) = column(i) {
+            let embedding = self
+                .embeddings
+                .get(self.rowid as usize)
+                .expect("Internal rembed_batch logic error");
+            api::result_blob(context, &self.vector_type.to_blob(embedding));
+            api::result_subtype(context, self.vector_type.subtype());
+        }
+        Ok(())
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.rowid)
+    }
+}
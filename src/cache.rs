@@ -0,0 +1,117 @@
+use ring::digest::{Context, SHA256};
+use sqlite_loadable::{api, prelude::*, Result};
+use std::cell::Cell;
+
+use crate::sql::{execute, value_blob, Statement};
+
+/// Hit and miss counts for a client's cache lookups in this connection.
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub hits: Cell<i64>,
+    pub misses: Cell<i64>,
+}
+
+/// Identifies every embedding cached for one client configuration, so they can be cleared
+/// together with `rembed_cache_clear()`.
+pub fn namespace(fingerprint: &str) -> Vec<u8> {
+    ring::digest::digest(&SHA256, fingerprint.as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+/// The cache key of one input. Every part is length-prefixed so that different
+/// `(fingerprint, input_type, input)` tuples can never hash the same bytes.
+pub fn key(fingerprint: &str, input_type: Option<&str>, input: &str) -> Vec<u8> {
+    let mut context = Context::new(&SHA256);
+    for part in [fingerprint, input_type.unwrap_or(""), input] {
+        context.update(&(part.len() as u64).to_le_bytes());
+        context.update(part.as_bytes());
+    }
+    context.update(&[u8::from(input_type.is_some())]);
+    context.finish().as_ref().to_vec()
+}
+
+/// The `rembed_cache` table of one connection, created the first time a client with a
+/// cache uses it.
+#[derive(Default)]
+pub struct CacheTable {
+    created: Cell<bool>,
+}
+
+impl CacheTable {
+    fn create(&self, db: *mut sqlite3) -> Result<()> {
+        execute(
+            db,
+            "create table if not exists main.rembed_cache(
+                key blob primary key,
+                namespace blob not null,
+                embedding blob not null,
+                created_at integer not null
+            ) without rowid",
+        )?;
+        self.created.set(true);
+        Ok(())
+    }
+
+    /// Prepares `sql` against the cache table, creating it first if this connection hasn't
+    /// yet, or if it was dropped or rolled back since.
+    fn prepare(&self, db: *mut sqlite3, sql: &str) -> Result<Statement> {
+        if !self.created.get() {
+            self.create(db)?;
+        }
+        Statement::prepare(db, sql).or_else(|_| {
+            self.create(db)?;
+            Statement::prepare(db, sql)
+        })
+    }
+
+    pub fn get(&self, db: *mut sqlite3, key: &[u8]) -> Result<Option<Vec<f32>>> {
+        let mut stmt = self.prepare(db, "select embedding from main.rembed_cache where key = ?")?;
+        stmt.bind_blob(1, key)?;
+        if !stmt.step()? {
+            return Ok(None);
+        }
+        let bytes = value_blob(&stmt.column_value(0));
+        Ok(Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        ))
+    }
+
+    pub fn put(
+        &self,
+        db: *mut sqlite3,
+        key: &[u8],
+        namespace: &[u8],
+        embedding: &[u8],
+    ) -> Result<()> {
+        let mut stmt = self.prepare(
+            db,
+            "insert or replace into main.rembed_cache(key, namespace, embedding, created_at)
+            values (?, ?, ?, cast(strftime('%s', 'now') as integer))",
+        )?;
+        stmt.bind_blob(1, key)?;
+        stmt.bind_blob(2, namespace)?;
+        stmt.bind_blob(3, embedding)?;
+        while stmt.step()? {}
+        Ok(())
+    }
+
+    /// Deletes every cached embedding in `namespace`, returning how many were removed.
+    pub fn clear(&self, db: *mut sqlite3, namespace: &[u8]) -> Result<i64> {
+        let mut stmt = self.prepare(
+            db,
+            "select count(*) from main.rembed_cache where namespace = ?",
+        )?;
+        stmt.bind_blob(1, namespace)?;
+        stmt.step()?;
+        let count = api::value_int64(&stmt.column_value(0));
+        drop(stmt);
+        let mut stmt = self.prepare(db, "delete from main.rembed_cache where namespace = ?")?;
+        stmt.bind_blob(1, namespace)?;
+        while stmt.step()? {}
+        Ok(count)
+    }
+}
@@ -0,0 +1,98 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_data_embeddings, try_env_var, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_API_KEY_ENV: &str = "AZURE_OPENAI_API_KEY";
+const DEFAULT_API_VERSION: &str = "2024-10-21";
+
+/// [Azure OpenAI embeddings](https://learn.microsoft.com/en-us/azure/ai-services/openai/reference#embeddings).
+/// Requests are sent to a deployment, `https://{resource}.openai.azure.com/openai/deployments/{deployment}/embeddings`,
+/// authenticated with an `api-key` header or with a Microsoft Entra ID bearer token.
+pub struct AzureClient {
+    deployment: String,
+    endpoint: HttpEndpoint,
+    /// Whether `endpoint.key` is an Entra ID token rather than an API key.
+    entra_id: bool,
+}
+
+impl AzureClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let deployment = match config.option("deployment") {
+            Some(deployment) => deployment.to_owned(),
+            None => config.model.clone().ok_or_else(|| {
+                Error::new_message("'deployment' option is required for the 'azure' format")
+            })?,
+        };
+        let url = match &config.url {
+            Some(url) => url.clone(),
+            None => {
+                let resource = config.option("resource").ok_or_else(|| {
+                    Error::new_message(
+                        "'resource' or 'url' option is required for the 'azure' format",
+                    )
+                })?;
+                format!("https://{resource}.openai.azure.com/openai/deployments/{deployment}/embeddings")
+            }
+        };
+        let url = if url.contains("api-version=") {
+            url
+        } else {
+            let api_version = config.option("api_version").unwrap_or(DEFAULT_API_VERSION);
+            let separator = if url.contains('?') { '&' } else { '?' };
+            format!("{url}{separator}api-version={api_version}")
+        };
+
+        let token = match (config.option("token_env"), config.option("token")) {
+            (Some(var), _) => Some(try_env_var(var)?),
+            (None, token) => token.map(|token| token.to_owned()),
+        };
+        let entra_id = token.is_some();
+        let mut endpoint = config.endpoint(&url, (!entra_id).then_some(DEFAULT_API_KEY_ENV))?;
+        endpoint.url = url;
+        if entra_id {
+            endpoint.key = token;
+        }
+
+        Ok(Self {
+            deployment,
+            endpoint,
+            entra_id,
+        })
+    }
+}
+
+impl EmbeddingClient for AzureClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let body = serde_json::json!({ "input": inputs });
+        let data = if self.entra_id {
+            self.endpoint.post(body, inputs)?
+        } else {
+            let mut request = self.endpoint.request(&self.endpoint.url);
+            if let Some(key) = &self.endpoint.key {
+                request = request.set("api-key", key);
+            }
+            self.endpoint.send(request, body, inputs)?
+        };
+        parse_data_embeddings(data)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        2048
+    }
+
+    fn format(&self) -> &str {
+        "azure"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.deployment)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
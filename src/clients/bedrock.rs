@@ -0,0 +1,139 @@
+use sqlite_loadable::{Error, Result};
+use std::time::SystemTime;
+
+use super::{parse_embedding, parse_embeddings_array, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::sigv4::{self, uri_encode, Credentials};
+
+const SERVICE: &str = "bedrock";
+
+/// The request and response shapes of the embedding models on Bedrock.
+#[derive(Clone, Copy, PartialEq)]
+enum ModelFamily {
+    /// `amazon.titan-embed-*`, one `inputText` per request.
+    Titan,
+    /// `cohere.embed-*`, a batch of `texts` with a required `input_type`.
+    Cohere,
+}
+
+/// [Amazon Bedrock embeddings](https://docs.aws.amazon.com/bedrock/latest/userguide/titan-embedding-models.html),
+/// through `InvokeModel` requests signed with AWS Signature Version 4.
+pub struct BedrockClient {
+    model: String,
+    family: ModelFamily,
+    region: String,
+    credentials: Credentials,
+    endpoint: HttpEndpoint,
+}
+
+impl BedrockClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let model = config.model()?;
+        let family = if model.contains("amazon.titan-embed") {
+            ModelFamily::Titan
+        } else if model.contains("cohere.embed") {
+            ModelFamily::Cohere
+        } else {
+            return Err(Error::new_message(format!(
+                "Unsupported bedrock model '{model}', expected an amazon.titan-embed or cohere.embed model"
+            )));
+        };
+        let region = match config.option("region") {
+            Some(region) => region.to_owned(),
+            None => std::env::var("AWS_REGION")
+                .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                .map_err(|_| {
+                    Error::new_message(
+                        "'region' option is required for the 'bedrock' format, or the AWS_REGION environment variable",
+                    )
+                })?,
+        };
+        let default_url = format!("https://bedrock-runtime.{region}.amazonaws.com");
+        Ok(Self {
+            model,
+            family,
+            region,
+            credentials: Credentials::from_env()?,
+            endpoint: config.endpoint(&default_url, None)?,
+        })
+    }
+
+    fn invoke(&self, body: serde_json::Value, inputs: &[&str]) -> Result<serde_json::Value> {
+        let base = self.endpoint.url.trim_end_matches('/');
+        let path = format!("/model/{}/invoke", uri_encode(&self.model));
+        let host = base
+            .split_once("://")
+            .map_or(base, |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        // Signed over the same bytes that the transport sends
+        let bytes = serde_json::to_vec(&body).map_err(|error| {
+            Error::new_message(format!("Error serializing body to JSON: {error}"))
+        })?;
+        let headers = sigv4::sign_post(
+            &self.credentials,
+            &self.region,
+            SERVICE,
+            host,
+            &path,
+            &bytes,
+            SystemTime::now(),
+        );
+        let mut request = self.endpoint.request(&format!("{base}{path}"));
+        for (name, value) in headers {
+            request = request.set(name, &value);
+        }
+        self.endpoint.send(request, body, inputs)
+    }
+}
+
+impl EmbeddingClient for BedrockClient {
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        match self.family {
+            ModelFamily::Titan => inputs
+                .iter()
+                .map(|input| {
+                    let data = self.invoke(serde_json::json!({ "inputText": input }), &[input])?;
+                    let embedding = data.get("embedding").ok_or_else(|| {
+                        Error::new_message("expected 'embedding' key in response body")
+                    })?;
+                    parse_embedding(embedding, "embedding")
+                })
+                .collect(),
+            ModelFamily::Cohere => {
+                let body = serde_json::json!({
+                    "texts": inputs,
+                    "input_type": input_type.unwrap_or("search_document"),
+                });
+                parse_embeddings_array(self.invoke(body, inputs)?)
+            }
+        }
+    }
+
+    fn supports_input_type(&self) -> bool {
+        self.family == ModelFamily::Cohere
+    }
+
+    fn max_batch_size(&self) -> usize {
+        match self.family {
+            ModelFamily::Titan => 1,
+            ModelFamily::Cohere => 96,
+        }
+    }
+
+    fn format(&self) -> &str {
+        "bedrock"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,158 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_embeddings_array, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::vector::{unpack_provider_bits, VectorType};
+
+const DEFAULT_URL: &str = "https://api.cohere.com/v1/embed";
+const DEFAULT_V2_URL: &str = "https://api.cohere.com/v2/embed";
+const DEFAULT_API_KEY_ENV: &str = "CO_API_KEY";
+const API_VERSIONS: &[&str] = &["v1", "v2"];
+const EMBEDDING_TYPES: &[&str] = &["float", "int8", "binary", "ubinary"];
+const TRUNCATE_MODES: &[&str] = &["NONE", "START", "END"];
+/// The input type that embeds `images`, as data URIs, rather than `texts`.
+const IMAGE_INPUT_TYPE: &str = "image";
+/// The input type sent to the v2 API, which requires one, when none is given, like the
+/// Bedrock client does for Cohere models.
+const DEFAULT_V2_INPUT_TYPE: &str = "search_document";
+
+/// [Cohere embeddings](https://docs.cohere.com/reference/embed), through the v1 or v2 API.
+pub struct CohereClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// Whether requests go to the v2 API, from the `api_version` option.
+    v2: bool,
+    /// The `embedding_type` option, one of [`EMBEDDING_TYPES`], or else the one matching the
+    /// `output` option.
+    embedding_type: Option<String>,
+    truncate: Option<String>,
+}
+
+impl CohereClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let v2 = config
+            .choice_option("api_version", API_VERSIONS)?
+            .as_deref()
+            == Some("v2");
+        let default_url = if v2 { DEFAULT_V2_URL } else { DEFAULT_URL };
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(default_url, Some(DEFAULT_API_KEY_ENV))?,
+            v2,
+            embedding_type: match config.choice_option("embedding_type", EMBEDDING_TYPES)? {
+                Some(embedding_type) => Some(embedding_type),
+                None => match config.output {
+                    Some(VectorType::Int8) => Some("int8".to_owned()),
+                    Some(VectorType::Bit) => Some("ubinary".to_owned()),
+                    _ => None,
+                },
+            },
+            truncate: config.choice_option("truncate", TRUNCATE_MODES)?,
+        })
+    }
+
+    fn embed(&self, key: &str, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert(key.to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+
+        let input_type = match self.v2 {
+            true => Some(input_type.unwrap_or(DEFAULT_V2_INPUT_TYPE)),
+            false => input_type,
+        };
+        if let Some(input_type) = input_type {
+            body.insert("input_type".to_owned(), input_type.to_owned().into());
+        }
+        let embedding_type = match self.v2 {
+            true => Some(self.embedding_type.as_deref().unwrap_or("float")),
+            false => self.embedding_type.as_deref(),
+        };
+        if let Some(embedding_type) = embedding_type {
+            body.insert("embedding_types".to_owned(), vec![embedding_type].into());
+        }
+        if let Some(truncate) = &self.truncate {
+            body.insert("truncate".to_owned(), truncate.to_owned().into());
+        }
+
+        let mut data = self.endpoint.post(body.into(), inputs)?;
+        // With `embedding_types`, and always in v2, embeddings are keyed by their type
+        if let Some(embeddings) = data.get_mut("embeddings").filter(|e| e.is_object()) {
+            let embedding_type = self.embedding_type.as_deref().unwrap_or("float");
+            let typed = embeddings.get(embedding_type).cloned().ok_or_else(|| {
+                Error::new_message(format!(
+                    "expected 'embeddings.{embedding_type}' key in response body"
+                ))
+            })?;
+            *embeddings = typed;
+        }
+        let mut embeddings = parse_embeddings_array(data)?;
+        if let Some(embedding_type @ ("binary" | "ubinary")) = self.embedding_type.as_deref() {
+            for embedding in &mut embeddings {
+                unpack_provider_bits(embedding, embedding_type == "binary");
+            }
+        }
+        Ok(embeddings)
+    }
+}
+
+impl EmbeddingClient for CohereClient {
+    /// Inputs are embedded as images when `input_type` is `image`, one per request.
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        if input_type == Some(IMAGE_INPUT_TYPE) {
+            let mut embeddings = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                embeddings.extend(self.embed("images", &[input], input_type)?);
+            }
+            return Ok(embeddings);
+        }
+        self.embed("texts", inputs, input_type)
+    }
+
+    fn supports_input_type(&self) -> bool {
+        true
+    }
+
+    fn max_batch_size(&self) -> usize {
+        96
+    }
+
+    fn vector_type(&self) -> VectorType {
+        match self.embedding_type.as_deref() {
+            Some("int8") => VectorType::Int8,
+            Some("binary" | "ubinary") => VectorType::Bit,
+            _ => VectorType::Float32,
+        }
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if self.v2 {
+            parameters.insert("api_version".to_owned(), "v2".into());
+        }
+        if let Some(embedding_type) = &self.embedding_type {
+            parameters.insert(
+                "embedding_type".to_owned(),
+                embedding_type.to_owned().into(),
+            );
+        }
+        if let Some(truncate) = &self.truncate {
+            parameters.insert("truncate".to_owned(), truncate.to_owned().into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "cohere"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
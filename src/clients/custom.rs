@@ -0,0 +1,271 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_embedding, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const HEADER_PREFIX: &str = "header:";
+const DEFAULT_BATCH_SIZE: usize = 16;
+
+/// One step of a `embeddings_path`, like `.data`, `[0]` or `[*]`.
+#[derive(Debug)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Parses a JSON path like `$.data[*].embedding` into its segments.
+fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || Error::new_message(format!("Invalid 'embeddings_path' option '{path}'"));
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = vec![];
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix('.') {
+            let end = tail.find(['.', '[']).unwrap_or(tail.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(tail[..end].to_owned()));
+            rest = &tail[end..];
+        } else if let Some(tail) = rest.strip_prefix('[') {
+            let end = tail.find(']').ok_or_else(invalid)?;
+            segments.push(match &tail[..end] {
+                "*" => PathSegment::Wildcard,
+                index => PathSegment::Index(index.parse().map_err(|_| invalid())?),
+            });
+            rest = &tail[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+/// Every value in `value` that `segments` match, in document order.
+fn select<'a>(
+    value: &'a serde_json::Value,
+    segments: &[PathSegment],
+) -> Vec<&'a serde_json::Value> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return vec![value];
+    };
+    match segment {
+        PathSegment::Key(key) => value.get(key).map(|v| select(v, rest)).unwrap_or_default(),
+        PathSegment::Index(index) => value
+            .get(index)
+            .map(|v| select(v, rest))
+            .unwrap_or_default(),
+        PathSegment::Wildcard => value
+            .as_array()
+            .map(|items| items.iter().flat_map(|v| select(v, rest)).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// The values substituted into templates for one request.
+struct TemplateValues<'a> {
+    inputs: &'a [&'a str],
+    input_type: Option<&'a str>,
+    model: Option<&'a str>,
+    key: Option<&'a str>,
+}
+
+impl TemplateValues<'_> {
+    /// The text value of a `{{placeholder}}`, or `None` for unknown ones.
+    fn text_value(&self, placeholder: &str) -> Option<&str> {
+        match placeholder {
+            "input" => Some(self.inputs.first().copied().unwrap_or_default()),
+            "input_type" => Some(self.input_type.unwrap_or_default()),
+            "model" => Some(self.model.unwrap_or_default()),
+            "key" => Some(self.key.unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    /// Substitutes every `{{placeholder}}` in `template` with its text value, in a single
+    /// pass, so placeholders inside substituted values (like an input) are left as they are.
+    /// Unknown placeholders are kept as written.
+    fn render_text(&self, template: &str) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            rendered.push_str(&rest[..start]);
+            let tail = &rest[start + 2..];
+            match tail
+                .find("}}")
+                .and_then(|end| Some((end, self.text_value(&tail[..end])?)))
+            {
+                Some((end, value)) => {
+                    rendered.push_str(value);
+                    rest = &tail[end + 2..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[start + 1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    /// Renders a JSON body template. A string that is exactly `{{inputs}}` becomes an array of
+    /// every input, and one that is exactly `{{input_type}}` becomes `null` without an input
+    /// type. Placeholders inside other strings are substituted as text.
+    fn render_json(&self, template: &serde_json::Value) -> serde_json::Value {
+        match template {
+            serde_json::Value::String(s) if s == "{{inputs}}" => self.inputs.into(),
+            serde_json::Value::String(s) if s == "{{input_type}}" => self.input_type.into(),
+            serde_json::Value::String(s) => self.render_text(s).into(),
+            serde_json::Value::Array(items) => {
+                items.iter().map(|item| self.render_json(item)).collect()
+            }
+            serde_json::Value::Object(fields) => fields
+                .iter()
+                .map(|(key, value)| (key.clone(), self.render_json(value)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            value => value.clone(),
+        }
+    }
+}
+
+/// A client for any JSON embeddings API, described by a request body template, header
+/// templates, and a JSON path to the embeddings in the response.
+pub struct CustomClient {
+    model: Option<String>,
+    endpoint: HttpEndpoint,
+    body: serde_json::Value,
+    /// The `body` option as given.
+    body_template: String,
+    headers: Vec<(String, String)>,
+    embeddings_path: String,
+    path: Vec<PathSegment>,
+    /// Whether `body` has an `{{inputs}}` placeholder, rather than `{{input}}`.
+    batched: bool,
+    batch_size: usize,
+}
+
+impl CustomClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let url = config.url.clone().ok_or_else(|| {
+            Error::new_message("'url' option is required for the 'custom' format")
+        })?;
+        let body_template = config
+            .option("body")
+            .ok_or_else(|| Error::new_message("'body' option is required for the 'custom' format"))?
+            .to_owned();
+        let body = serde_json::from_str(&body_template).map_err(|error| {
+            Error::new_message(format!("'body' option must be a JSON template: {error}"))
+        })?;
+        let batched = body_template.contains("\"{{inputs}}\"");
+        if !batched && !body_template.contains("{{input}}") {
+            return Err(Error::new_message(
+                "'body' option must contain an {{input}} or \"{{inputs}}\" placeholder",
+            ));
+        }
+        let embeddings_path = config
+            .option("embeddings_path")
+            .ok_or_else(|| {
+                Error::new_message("'embeddings_path' option is required for the 'custom' format")
+            })?
+            .to_owned();
+        let mut headers: Vec<(String, String)> = config
+            .options
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(HEADER_PREFIX)
+                    .map(|name| (name.to_owned(), value.to_owned()))
+            })
+            .collect();
+        headers.sort();
+
+        Ok(Self {
+            model: config.model.clone(),
+            endpoint: config.endpoint(&url, None)?,
+            body,
+            body_template,
+            headers,
+            path: parse_path(&embeddings_path)?,
+            embeddings_path,
+            batched,
+            batch_size: config
+                .parse_option("batch_size")?
+                .unwrap_or(DEFAULT_BATCH_SIZE),
+        })
+    }
+}
+
+impl EmbeddingClient for CustomClient {
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let values = TemplateValues {
+            inputs,
+            input_type,
+            model: self.model.as_deref(),
+            key: self.endpoint.key.as_deref(),
+        };
+        let url = values.render_text(&self.endpoint.url);
+        // Without any header options, the API key is sent as a bearer token
+        let request = if self.headers.is_empty() {
+            self.endpoint.authorized_request(&url)
+        } else {
+            self.headers
+                .iter()
+                .fold(self.endpoint.request(&url), |request, (name, value)| {
+                    request.set(name, &values.render_text(value))
+                })
+        };
+        let data = self
+            .endpoint
+            .send(request, values.render_json(&self.body), inputs)?;
+
+        let embeddings = select(&data, &self.path);
+        if embeddings.is_empty() {
+            return Err(Error::new_message(format!(
+                "expected '{}' path in response body",
+                self.embeddings_path
+            )));
+        }
+        embeddings
+            .into_iter()
+            .map(|embedding| parse_embedding(embedding, &self.embeddings_path))
+            .collect()
+    }
+
+    fn supports_input_type(&self) -> bool {
+        self.body_template.contains("{{input_type}}")
+    }
+
+    fn max_batch_size(&self) -> usize {
+        if self.batched {
+            self.batch_size
+        } else {
+            1
+        }
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        parameters.insert("body".to_owned(), self.body.clone());
+        parameters.insert(
+            "embeddings_path".to_owned(),
+            self.embeddings_path.to_owned().into(),
+        );
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "custom"
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
@@ -0,0 +1,180 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_embedding, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const DEFAULT_API_KEY_ENV: &str = "GEMINI_API_KEY";
+const TASK_TYPES: &[&str] = &[
+    "RETRIEVAL_QUERY",
+    "RETRIEVAL_DOCUMENT",
+    "SEMANTIC_SIMILARITY",
+    "CLASSIFICATION",
+    "CLUSTERING",
+    "QUESTION_ANSWERING",
+    "FACT_VERIFICATION",
+    "CODE_RETRIEVAL_QUERY",
+];
+
+/// [Gemini embeddings](https://ai.google.dev/api/embeddings). Requests are sent to
+/// `{url}/{model}:embedContent` or `{url}/{model}:batchEmbedContents`, with the API key
+/// as the `key` query parameter.
+pub struct GeminiClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `output_dimensionality` option, for models that support several dimensions.
+    output_dimensionality: Option<usize>,
+}
+
+/// Maps an `input_type` to a Gemini `taskType`. Task types can be given in any case, and
+/// the `query`/`document` names used by other providers map to their retrieval task types.
+pub(super) fn task_type(input_type: &str) -> Result<&'static str> {
+    let input_type = input_type.to_ascii_uppercase();
+    let input_type = match input_type.as_str() {
+        "QUERY" | "SEARCH_QUERY" => "RETRIEVAL_QUERY",
+        "DOCUMENT" | "SEARCH_DOCUMENT" => "RETRIEVAL_DOCUMENT",
+        input_type => input_type,
+    };
+    TASK_TYPES
+        .iter()
+        .find(|task_type| **task_type == input_type)
+        .copied()
+        .ok_or_else(|| {
+            Error::new_message(format!(
+                "Unknown input type '{input_type}' for the 'gemini' format, expected one of {}",
+                TASK_TYPES.join(", ")
+            ))
+        })
+}
+
+impl GeminiClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(DEFAULT_URL, Some(DEFAULT_API_KEY_ENV))?,
+            output_dimensionality: config.parse_option("output_dimensionality")?,
+        })
+    }
+
+    /// The model name as Gemini expects it in request bodies, like `models/text-embedding-004`.
+    fn model_path(&self) -> String {
+        if self.model.starts_with("models/") {
+            self.model.clone()
+        } else {
+            format!("models/{}", self.model)
+        }
+    }
+
+    /// A single `embedContent` request, also used for each input of `batchEmbedContents`.
+    fn content_request(&self, input: &str, input_type: Option<&str>) -> Result<serde_json::Value> {
+        let mut request = serde_json::Map::new();
+        request.insert("model".to_owned(), self.model_path().into());
+        request.insert(
+            "content".to_owned(),
+            serde_json::json!({ "parts": [{ "text": input }] }),
+        );
+        if let Some(input_type) = input_type {
+            request.insert("taskType".to_owned(), task_type(input_type)?.into());
+        }
+        if let Some(output_dimensionality) = self.output_dimensionality {
+            request.insert(
+                "outputDimensionality".to_owned(),
+                output_dimensionality.into(),
+            );
+        }
+        Ok(request.into())
+    }
+
+    fn post(
+        &self,
+        method: &str,
+        body: serde_json::Value,
+        inputs: &[&str],
+    ) -> Result<serde_json::Value> {
+        let model = self.model.trim_start_matches("models/");
+        let mut request = self
+            .endpoint
+            .request(&format!("{}/{model}:{method}", self.endpoint.url));
+        if let Some(key) = &self.endpoint.key {
+            request = request.query("key", key);
+        }
+        self.endpoint.send(request, body, inputs)
+    }
+}
+
+/// Parses a `ContentEmbedding` object, `{"values": [...]}`, at `path` in a response body.
+fn parse_content_embedding(value: &serde_json::Value, path: &str) -> Result<Vec<f32>> {
+    let values = value.get("values").ok_or_else(|| {
+        Error::new_message(format!("expected '{path}.values' path in response body"))
+    })?;
+    parse_embedding(values, &format!("{path}.values"))
+}
+
+impl EmbeddingClient for GeminiClient {
+    fn infer(&self, input: &str, input_type: Option<&str>) -> Result<Vec<f32>> {
+        let body = self.content_request(input, input_type)?;
+        let data = self.post("embedContent", body, &[input])?;
+        let embedding = data
+            .get("embedding")
+            .ok_or_else(|| Error::new_message("expected 'embedding' key in response body"))?;
+        parse_content_embedding(embedding, "embedding")
+    }
+
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let requests = inputs
+            .iter()
+            .map(|input| self.content_request(input, input_type))
+            .collect::<Result<Vec<_>>>()?;
+        let data = self.post(
+            "batchEmbedContents",
+            serde_json::json!({ "requests": requests }),
+            inputs,
+        )?;
+        data.get("embeddings")
+            .ok_or_else(|| Error::new_message("expected 'embeddings' key in response body"))?
+            .as_array()
+            .ok_or_else(|| Error::new_message("expected 'embeddings' path to be an array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| parse_content_embedding(embedding, &format!("embeddings.{i}")))
+            .collect()
+    }
+
+    fn supports_input_type(&self) -> bool {
+        true
+    }
+
+    fn max_batch_size(&self) -> usize {
+        100
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.output_dimensionality
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(output_dimensionality) = self.output_dimensionality {
+            parameters.insert(
+                "output_dimensionality".to_owned(),
+                output_dimensionality.into(),
+            );
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "gemini"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
@@ -0,0 +1,68 @@
+use sqlite_loadable::Result;
+
+use super::{parse_data_embeddings, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_URL: &str = "https://api.jina.ai/v1/embeddings";
+const DEFAULT_API_KEY_ENV: &str = "JINA_API_KEY";
+
+/// [Jina embeddings](https://api.jina.ai/redoc#tag/embeddings), which use the same request
+/// and response bodies as OpenAI.
+pub struct JinaClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    dimensions: Option<usize>,
+}
+
+impl JinaClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(DEFAULT_URL, Some(DEFAULT_API_KEY_ENV))?,
+            dimensions: config.parse_option("dimensions")?,
+        })
+    }
+}
+
+impl EmbeddingClient for JinaClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert("input".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+        if let Some(dimensions) = self.dimensions {
+            body.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parse_data_embeddings(self.endpoint.post(body.into(), inputs)?)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        2048
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(dimensions) = self.dimensions {
+            parameters.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "jina"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
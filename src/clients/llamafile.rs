@@ -0,0 +1,121 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_data_embeddings, parse_embedding, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_URL: &str = "http://localhost:8080/embedding";
+const OPENAI_PATH: &str = "/v1/embeddings";
+const OPENAI_BATCH_SIZE: usize = 256;
+
+/// A local [llamafile](https://github.com/Mozilla-Ocho/llamafile) or llama.cpp server.
+/// Requests go to the native `/embedding` endpoint, or to the OpenAI-compatible
+/// `/v1/embeddings` endpoint when `url` points at it. The `model` option selects a model
+/// on servers that host several.
+pub struct LlamafileClient {
+    model: Option<String>,
+    endpoint: HttpEndpoint,
+    /// Whether `url` is an OpenAI-compatible `/v1/embeddings` endpoint.
+    openai: bool,
+    batch_size: usize,
+}
+
+impl LlamafileClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let endpoint = config.endpoint(DEFAULT_URL, None)?;
+        let openai = endpoint.url.trim_end_matches('/').ends_with(OPENAI_PATH);
+        // Older servers only accept a single `content` string, so batches are opt-in there
+        let default_batch_size = if openai { OPENAI_BATCH_SIZE } else { 1 };
+        Ok(Self {
+            model: config.model.clone(),
+            endpoint,
+            openai,
+            batch_size: config
+                .parse_option("batch_size")?
+                .unwrap_or(default_batch_size),
+        })
+    }
+}
+
+/// Parses the `embedding` of one input from the native endpoint. llama.cpp servers return
+/// one row per pooled sequence, so a nested array must hold exactly one row, which isn't
+/// the case for servers started with `--pooling none`.
+fn parse_native_embedding(value: &serde_json::Value, path: &str) -> Result<Vec<f32>> {
+    match value.as_array().map(|rows| rows.as_slice()) {
+        Some([row]) if row.is_array() => parse_embedding(row, &format!("{path}.0")),
+        Some([first, ..]) if first.is_array() => Err(Error::new_message(format!(
+            "expected '{path}' to hold a single pooled embedding, found one per token. Start the server with a --pooling option other than none"
+        ))),
+        _ => parse_embedding(value, path),
+    }
+}
+
+/// Parses a response from the native endpoint: `{"embedding": [...]}` from llamafile and
+/// older llama.cpp servers, or `[{"index": 0, "embedding": [[...]]}, ...]` from newer ones.
+fn parse_native_response(value: serde_json::Value) -> Result<Vec<Vec<f32>>> {
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        item => vec![item],
+    };
+    let mut embeddings = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = item
+                .get("index")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+                .unwrap_or(i);
+            let embedding = item
+                .get("embedding")
+                .ok_or_else(|| Error::new_message("expected 'embedding' key in response body"))?;
+            Ok((index, parse_native_embedding(embedding, "embedding")?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    embeddings.sort_by_key(|(index, _)| *index);
+    Ok(embeddings
+        .into_iter()
+        .map(|(_, embedding)| embedding)
+        .collect())
+}
+
+impl EmbeddingClient for LlamafileClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        if self.openai {
+            body.insert("input".to_owned(), inputs.into());
+        } else if let [input] = inputs {
+            body.insert("content".to_owned(), (*input).into());
+        } else {
+            body.insert("content".to_owned(), inputs.into());
+        }
+        if let Some(model) = &self.model {
+            body.insert("model".to_owned(), model.to_owned().into());
+        }
+
+        let data = self.endpoint.post(body.into(), inputs)?;
+        if self.openai {
+            parse_data_embeddings(data)
+        } else {
+            parse_native_response(data)
+        }
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    fn format(&self) -> &str {
+        "llamafile"
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
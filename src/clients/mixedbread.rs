@@ -0,0 +1,79 @@
+use sqlite_loadable::Result;
+
+use super::{parse_data_embeddings, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::vector::{unpack_provider_bits, VectorType};
+
+const DEFAULT_URL: &str = "https://api.mixedbread.ai/v1/embeddings/";
+const DEFAULT_API_KEY_ENV: &str = "MIXEDBREAD_API_KEY";
+
+/// [Mixedbread embeddings](https://www.mixedbread.ai/api-reference#quick-start-guide), which use
+/// the same request and response bodies as OpenAI.
+pub struct MixedbreadClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `output` option, requested as the matching `encoding_format`.
+    output: VectorType,
+}
+
+impl MixedbreadClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(DEFAULT_URL, Some(DEFAULT_API_KEY_ENV))?,
+            output: config.output.unwrap_or_default(),
+        })
+    }
+}
+
+impl EmbeddingClient for MixedbreadClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert("input".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+        body.extend(self.parameters());
+        let mut embeddings = parse_data_embeddings(self.endpoint.post(body.into(), inputs)?)?;
+        if self.output == VectorType::Bit {
+            for embedding in &mut embeddings {
+                unpack_provider_bits(embedding, false);
+            }
+        }
+        Ok(embeddings)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        256
+    }
+
+    fn vector_type(&self) -> VectorType {
+        self.output
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        let encoding_format = match self.output {
+            VectorType::Float32 => None,
+            VectorType::Int8 => Some("int8"),
+            VectorType::Bit => Some("ubinary"),
+        };
+        if let Some(encoding_format) = encoding_format {
+            parameters.insert("encoding_format".to_owned(), encoding_format.into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "mixedbread"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
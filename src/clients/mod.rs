@@ -0,0 +1,786 @@
+use sqlite_loadable::{Error, ErrorKind, Result};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use crate::{
+    cache::{CacheStats, CacheTable},
+    http::{parse_bool_option, parse_option, Transport, UnlessStatus},
+    rate_limit::estimate_tokens,
+    vector::{normalize, VectorType},
+};
+
+mod azure;
+mod bedrock;
+mod cohere;
+mod custom;
+mod gemini;
+mod jina;
+mod llamafile;
+mod mixedbread;
+mod nomic;
+mod ollama;
+mod openai;
+mod tei;
+mod vertex;
+mod voyage;
+
+pub use azure::AzureClient;
+pub use bedrock::BedrockClient;
+pub use cohere::CohereClient;
+pub use custom::CustomClient;
+pub use gemini::GeminiClient;
+pub use jina::JinaClient;
+pub use llamafile::LlamafileClient;
+pub use mixedbread::MixedbreadClient;
+pub use nomic::NomicClient;
+pub use ollama::OllamaClient;
+pub use openai::OpenAiClient;
+pub use tei::TeiClient;
+pub use vertex::VertexClient;
+pub use voyage::VoyageClient;
+
+/// A service that turns text into embeddings. Every `format` accepted by
+/// `rembed_client_options()` is an implementation of this trait.
+pub trait EmbeddingClient {
+    /// Embeds a single input. Defaults to a batch of one.
+    fn infer(&self, input: &str, input_type: Option<&str>) -> Result<Vec<f32>> {
+        single_embedding(self.infer_batch(&[input], input_type)?)
+    }
+
+    /// Embeds every input in a single request, returning embeddings in the same order.
+    /// `input_type` is ignored by clients that don't [support it](Self::supports_input_type).
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>>;
+
+    /// Whether the provider accepts an `input_type`, like `search_query`.
+    fn supports_input_type(&self) -> bool {
+        false
+    }
+
+    /// The maximum number of inputs passed to a single [`infer_batch`](Self::infer_batch).
+    fn max_batch_size(&self) -> usize {
+        1
+    }
+
+    /// The number of dimensions of the returned embeddings, when known ahead of time.
+    fn dimensions(&self) -> Option<usize> {
+        None
+    }
+
+    /// The element type of the returned embeddings. Embeddings are always returned as `f32`
+    /// values, which are encoded as this type's `sqlite-vec` BLOB.
+    fn vector_type(&self) -> VectorType {
+        VectorType::Float32
+    }
+
+    /// Provider-specific options that change the returned embeddings, like a reduced number
+    /// of dimensions. They're shown in `rembed_clients` and are part of the cache fingerprint.
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        serde_json::Map::new()
+    }
+
+    /// The `format` name shown in `rembed_clients`.
+    fn format(&self) -> &str;
+
+    /// The model requested from the provider, if any.
+    fn model(&self) -> Option<&str> {
+        None
+    }
+
+    /// The endpoint requests are sent to, if any.
+    fn url(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether requests are sent with an API key.
+    fn key_set(&self) -> bool {
+        false
+    }
+}
+
+/// Options holding credentials, which are never written to a persistent `rembed_clients`
+/// table. Each can be read from an environment variable with a `_env` option instead.
+pub(crate) const SECRET_OPTIONS: &[&str] = &["key", "token"];
+
+pub(crate) fn try_env_var(key: &str) -> Result<String> {
+    std::env::var(key)
+   .map_err(|_| Error::new_message(format!("{key} environment variable not defined. Alternatively, pass in an API key with rembed_client_options")))
+}
+
+/// Parses a JSON array of numbers at `path` in a response body into an embedding.
+fn parse_embedding(value: &serde_json::Value, path: &str) -> Result<Vec<f32>> {
+    value
+        .as_array()
+        .ok_or_else(|| Error::new_message(format!("expected '{path}' path to be an array")))
+        .and_then(|arr| {
+            arr.iter()
+                .map(|v| {
+                    v.as_f64()
+                        .ok_or_else(|| {
+                            Error::new_message(format!("expected '{path}' array to contain floats"))
+                        })
+                        .map(|f| f as f32)
+                })
+                .collect()
+        })
+}
+
+/// Parses an OpenAI-style `{"data": [{"index": 0, "embedding": [...]}, ...]}` response
+/// body, ordering the embeddings by their `index` key.
+fn parse_data_embeddings(value: serde_json::Value) -> Result<Vec<Vec<f32>>> {
+    let data = value
+        .get("data")
+        .ok_or_else(|| Error::new_message("expected 'data' key in response body"))?
+        .as_array()
+        .ok_or_else(|| Error::new_message("expected 'data' path to be an array"))?;
+    let mut embeddings = data
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = item
+                .get("index")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize)
+                .unwrap_or(i);
+            let embedding = item.get("embedding").ok_or_else(|| {
+                Error::new_message(format!(
+                    "expected 'data.{i}.embedding' path in response body"
+                ))
+            })?;
+            Ok((
+                index,
+                parse_embedding(embedding, &format!("data.{i}.embedding"))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    embeddings.sort_by_key(|(index, _)| *index);
+    Ok(embeddings
+        .into_iter()
+        .map(|(_, embedding)| embedding)
+        .collect())
+}
+
+/// Parses a `{"embeddings": [[...], ...]}` response body, as returned by Nomic and Cohere.
+fn parse_embeddings_array(value: serde_json::Value) -> Result<Vec<Vec<f32>>> {
+    value
+        .get("embeddings")
+        .ok_or_else(|| Error::new_message("expected 'embeddings' key in response body"))?
+        .as_array()
+        .ok_or_else(|| Error::new_message("expected 'embeddings' path to be an array"))?
+        .iter()
+        .enumerate()
+        .map(|(i, embedding)| parse_embedding(embedding, &format!("embeddings.{i}")))
+        .collect()
+}
+
+/// Returns the only embedding in a batch response for a single input.
+fn single_embedding(mut embeddings: Vec<Vec<f32>>) -> Result<Vec<f32>> {
+    match embeddings.len() {
+        1 => Ok(embeddings.remove(0)),
+        n => Err(Error::new_message(format!(
+            "expected 1 embedding in response body, found {n}"
+        ))),
+    }
+}
+
+/// The options every built-in client is constructed from.
+pub(crate) struct ClientConfig {
+    pub model: Option<String>,
+    pub url: Option<String>,
+    pub key: Option<String>,
+    pub transport: Transport,
+    /// The `output` option, for providers that can return quantized embeddings themselves.
+    pub output: Option<VectorType>,
+    /// Every `rembed_client_options()` key/value pair, for format-specific options.
+    pub options: HashMap<String, String>,
+}
+
+impl ClientConfig {
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|value| value.as_str())
+    }
+
+    pub fn parse_option<T: std::str::FromStr>(&self, key: &str) -> Result<Option<T>> {
+        parse_option(&self.options, key)
+    }
+
+    pub fn bool_option(&self, key: &str) -> Result<Option<bool>> {
+        parse_bool_option(&self.options, key)
+    }
+
+    /// The `key` option, which must be one of `allowed` when given.
+    pub fn choice_option(&self, key: &str, allowed: &[&str]) -> Result<Option<String>> {
+        match self.option(key) {
+            Some(value) if !allowed.contains(&value) => Err(Error::new_message(format!(
+                "Invalid value '{value}' for '{key}' option, expected one of {}",
+                allowed.join(", ")
+            ))),
+            value => Ok(value.map(|value| value.to_owned())),
+        }
+    }
+
+    pub fn model(&self) -> Result<String> {
+        self.model
+            .clone()
+            .ok_or_else(|| Error::new_message("'model' option is required"))
+    }
+
+    /// An endpoint at the `url` option or `default_url`. The API key comes from the `key`
+    /// option, or else from the `default_key_env` environment variable when given.
+    pub fn endpoint(
+        &self,
+        default_url: &str,
+        default_key_env: Option<&str>,
+    ) -> Result<HttpEndpoint> {
+        let key = match (&self.key, default_key_env) {
+            (Some(key), _) => Some(key.clone()),
+            (None, Some(var)) => Some(try_env_var(var)?),
+            (None, None) => None,
+        };
+        Ok(HttpEndpoint {
+            url: self.url.clone().unwrap_or(default_url.to_owned()),
+            key,
+            transport: self.transport.clone(),
+        })
+    }
+}
+
+/// Where a built-in client sends its requests.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpEndpoint {
+    pub url: String,
+    pub key: Option<String>,
+    pub transport: Transport,
+}
+
+impl HttpEndpoint {
+    /// Sends `body` as JSON, with the API key as a bearer token, and parses the JSON
+    /// response. `inputs` are only used to estimate tokens for the rate limiter.
+    pub fn post(&self, body: serde_json::Value, inputs: &[&str]) -> Result<serde_json::Value> {
+        self.send(self.authorized_request(&self.url), body, inputs)
+    }
+
+    /// A JSON POST request to `url`, for clients that authenticate differently or send
+    /// requests to more than one URL.
+    pub fn request(&self, url: &str) -> ureq::Request {
+        ureq::post(url)
+            .set("Content-Type", "application/json")
+            .set("Accept", "application/json")
+    }
+
+    /// A JSON POST request to `url`, with the API key as a bearer token.
+    pub fn authorized_request(&self, url: &str) -> ureq::Request {
+        let request = self.request(url);
+        match &self.key {
+            Some(key) => request.set("Authorization", &format!("Bearer {key}")),
+            None => request,
+        }
+    }
+
+    /// Sends `request` with `body` as JSON, and parses the JSON response. The API key is
+    /// redacted from errors, which include the request URL.
+    pub fn send(
+        &self,
+        request: ureq::Request,
+        body: serde_json::Value,
+        inputs: &[&str],
+    ) -> Result<serde_json::Value> {
+        self.send_unless_status(request, body, inputs, None)
+            .map(|data| data.expect("no status is accepted"))
+    }
+
+    /// Like [`send`](Self::send), but returns `None` when the server responds with the
+    /// status of `unless` and a body that its function accepts.
+    pub fn send_unless_status(
+        &self,
+        request: ureq::Request,
+        body: serde_json::Value,
+        inputs: &[&str],
+        unless: Option<UnlessStatus>,
+    ) -> Result<Option<serde_json::Value>> {
+        self.transport
+            .post_json(request, &body, estimate_tokens(inputs), unless)
+            .map_err(|error| match (&self.key, error.kind()) {
+                (Some(key), ErrorKind::Message(message)) if !key.is_empty() => {
+                    Error::new_message(message.replace(key.as_str(), "<redacted>"))
+                }
+                _ => error,
+            })
+    }
+
+    pub fn key_set(&self) -> bool {
+        self.key.as_ref().is_some_and(|key| !key.is_empty())
+    }
+}
+
+/// A client registered in `rembed_clients`, along with the settings that the SQL
+/// functions apply around its requests.
+#[derive(Clone)]
+pub struct RegisteredClient {
+    pub client: Rc<dyn EmbeddingClient>,
+    /// The retry and rate limit settings of built-in clients, for [`describe`](Self::describe).
+    pub transport: Option<Transport>,
+    /// When set, embeddings are read from and written to the `rembed_cache` table.
+    pub cache: Option<Rc<CacheStats>>,
+    /// The `input_type` used when `rembed()` or `rembed_batch()` aren't passed one.
+    pub input_type: Option<String>,
+    /// The `dimensions` option. Longer embeddings are truncated to this many dimensions and
+    /// re-normalized, for providers that can't shorten them themselves.
+    pub dimensions: Option<usize>,
+    /// The `normalize` option, which scales every embedding to unit length.
+    pub normalize: bool,
+    /// The `expected_dimensions` option. Embeddings of any other length are an error, instead
+    /// of a BLOB that a `vec0` table rejects later.
+    pub expected_dimensions: Option<usize>,
+    /// The type of the BLOBs returned by `rembed()` and `rembed_batch()`. Float embeddings
+    /// are quantized to it when the client returns [`VectorType::Float32`].
+    pub vector_type: VectorType,
+    /// The `rembed_client_options()` key/value pairs this client was built from, including
+    /// `format`. Empty for clients that weren't built from options.
+    pub definition: HashMap<String, String>,
+}
+
+impl RegisteredClient {
+    pub fn new(client: Rc<dyn EmbeddingClient>) -> Self {
+        Self {
+            transport: None,
+            cache: None,
+            input_type: None,
+            dimensions: None,
+            normalize: false,
+            expected_dimensions: None,
+            vector_type: client.vector_type(),
+            definition: HashMap::new(),
+            client,
+        }
+    }
+
+    /// Builds a client from `rembed_client_options()` key/value pairs. This is the only
+    /// place clients are constructed, so every option applies to every format. The API key
+    /// is read from the `key` option, or from the environment variable named by `key_env`,
+    /// or else from the format's default environment variable.
+    pub fn from_options(options: HashMap<String, String>) -> Result<Self> {
+        let format = options
+            .get("format")
+            .ok_or_else(|| Error::new_message("'format' key is required."))?;
+        let config = ClientConfig {
+            model: options.get("model").cloned(),
+            url: options.get("url").cloned(),
+            key: match options.get("key_env") {
+                Some(var) => Some(try_env_var(var)?),
+                None => options.get("key").cloned(),
+            },
+            transport: Transport::from_options(&options)?,
+            output: match options.get("output") {
+                Some(name) => Some(VectorType::from_name(name).ok_or_else(|| {
+                    Error::new_message(format!(
+                        "Invalid value '{name}' for 'output' option, expected one of float32, int8, bit"
+                    ))
+                })?),
+                None => None,
+            },
+            options: options.clone(),
+        };
+        let client: Rc<dyn EmbeddingClient> = match format.as_str() {
+            "openai" => Rc::new(OpenAiClient::new(&config)?),
+            "azure" => Rc::new(AzureClient::new(&config)?),
+            "bedrock" => Rc::new(BedrockClient::new(&config)?),
+            "jina" => Rc::new(JinaClient::new(&config)?),
+            "mixedbread" => Rc::new(MixedbreadClient::new(&config)?),
+            "nomic" => Rc::new(NomicClient::new(&config)?),
+            "cohere" => Rc::new(CohereClient::new(&config)?),
+            "gemini" => Rc::new(GeminiClient::new(&config)?),
+            "vertex" => Rc::new(VertexClient::new(&config)?),
+            "ollama" => Rc::new(OllamaClient::new(&config)?),
+            "llamafile" => Rc::new(LlamafileClient::new(&config)?),
+            "tei" => Rc::new(TeiClient::new(&config)?),
+            "custom" => Rc::new(CustomClient::new(&config)?),
+            "voyage" => Rc::new(VoyageClient::new(&config)?),
+            format => match OpenAiClient::from_preset(format, &config) {
+                Some(client) => Rc::new(client?),
+                None => return Err(Error::new_message(format!("Unknown format '{format}'"))),
+            },
+        };
+
+        let mut registered = RegisteredClient::new(client);
+        registered.transport = Some(config.transport);
+        if parse_bool_option(&options, "cache")?.unwrap_or(false) {
+            registered.cache = Some(Rc::new(CacheStats::default()));
+        }
+        if let Some(input_type) = options.get("input_type") {
+            if !registered.client.supports_input_type() {
+                return Err(Error::new_message(format!(
+                    "'input_type' option is not supported by the '{format}' format"
+                )));
+            }
+            registered.input_type = Some(input_type.to_owned());
+        }
+        registered.dimensions = parse_option(&options, "dimensions")?;
+        if let Some(dimensions) = registered.dimensions {
+            if dimensions == 0 {
+                return Err(Error::new_message(
+                    "'dimensions' option must be greater than 0",
+                ));
+            }
+            let vector_type = registered.client.vector_type();
+            if registered.client.dimensions() != Some(dimensions)
+                && vector_type != VectorType::Float32
+            {
+                return Err(Error::new_message(format!(
+                    "'dimensions' option can't be applied to {} embeddings from the '{format}' format",
+                    vector_type.name()
+                )));
+            }
+        }
+        registered.normalize = parse_bool_option(&options, "normalize")?.unwrap_or(false);
+        let vector_type = registered.client.vector_type();
+        if registered.normalize && vector_type != VectorType::Float32 {
+            return Err(Error::new_message(format!(
+                "'normalize' option can't be applied to {} embeddings from the '{format}' format",
+                vector_type.name()
+            )));
+        }
+        registered.expected_dimensions = parse_option(&options, "expected_dimensions")?;
+        if let Some(output) = config.output {
+            let vector_type = registered.client.vector_type();
+            if vector_type != output && vector_type != VectorType::Float32 {
+                return Err(Error::new_message(format!(
+                    "'output' option '{}' doesn't match the {} embeddings of the '{format}' format",
+                    output.name(),
+                    vector_type.name()
+                )));
+            }
+            registered.vector_type = output;
+        }
+        registered.definition = options;
+        Ok(registered)
+    }
+
+    /// Identifies the provider, model, endpoint and parameters of this client. Embeddings
+    /// cached for one fingerprint are never served for another.
+    pub fn fingerprint(&self) -> String {
+        let mut fingerprint = format!(
+            "{}\n{}\n{}",
+            self.client.format(),
+            self.client.model().unwrap_or(""),
+            self.client.url().unwrap_or("")
+        );
+        let mut parameters = self.client.parameters();
+        if let Some(dimensions) = self.dimensions {
+            parameters.insert("dimensions".to_owned(), dimensions.into());
+        }
+        if self.normalize {
+            parameters.insert("normalize".to_owned(), true.into());
+        }
+        if self.quantizes() {
+            parameters.insert("output".to_owned(), self.vector_type.name().into());
+        }
+        if !parameters.is_empty() {
+            fingerprint.push('\n');
+            fingerprint.push_str(&serde_json::Value::Object(parameters).to_string());
+        }
+        fingerprint
+    }
+
+    /// Embeds a single input, applying the `dimensions`, `normalize`, `expected_dimensions`
+    /// and `output` options.
+    pub fn infer(&self, input: &str, input_type: Option<&str>) -> Result<Vec<f32>> {
+        self.postprocess(self.client.infer(input, input_type)?)
+    }
+
+    /// Whether float embeddings from the client are quantized to [`vector_type`](Self::vector_type).
+    fn quantizes(&self) -> bool {
+        self.vector_type != self.client.vector_type()
+    }
+
+    /// Truncates, normalizes, checks the length of and then quantizes an embedding from the
+    /// provider, as the client's options ask.
+    fn postprocess(&self, embedding: Vec<f32>) -> Result<Vec<f32>> {
+        let mut embedding = self.resize(embedding)?;
+        if self.normalize {
+            normalize(&mut embedding);
+        }
+        if let Some(expected) = self.expected_dimensions {
+            let found = self.dimensions_of(&embedding);
+            if found != expected {
+                return Err(Error::new_message(format!(
+                    "expected {expected} dimensions in embedding, found {found}"
+                )));
+            }
+        }
+        Ok(match self.quantizes() {
+            true => self.vector_type.quantize(&embedding),
+            false => embedding,
+        })
+    }
+
+    /// The number of dimensions in an embedding from the client. Native `bit` embeddings
+    /// pack eight dimensions into each value.
+    fn dimensions_of(&self, embedding: &[f32]) -> usize {
+        match self.client.vector_type() {
+            VectorType::Bit => embedding.len() * 8,
+            _ => embedding.len(),
+        }
+    }
+
+    /// Truncates `embedding` to the `dimensions` option and re-normalizes it, unless the
+    /// provider already returned that many dimensions. Only float embeddings are truncated.
+    fn resize(&self, mut embedding: Vec<f32>) -> Result<Vec<f32>> {
+        let found = self.dimensions_of(&embedding);
+        match self.dimensions {
+            Some(dimensions) if found < dimensions => Err(Error::new_message(format!(
+                "expected at least {dimensions} dimensions in embedding, found {found}"
+            ))),
+            Some(dimensions)
+                if found > dimensions && self.client.vector_type() == VectorType::Float32 =>
+            {
+                embedding.truncate(dimensions);
+                normalize(&mut embedding);
+                Ok(embedding)
+            }
+            Some(dimensions) if found > dimensions => Err(Error::new_message(format!(
+                "expected {dimensions} dimensions in embedding, found {found}"
+            ))),
+            _ => Ok(embedding),
+        }
+    }
+
+    /// Embeds `inputs` in requests of at most `max_batch_size()` inputs each, returning
+    /// embeddings in the same order. The client's options are applied to each one, like in
+    /// [`infer`](Self::infer).
+    pub fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(self.client.max_batch_size().max(1)) {
+            let chunk_embeddings = self.client.infer_batch(chunk, input_type)?;
+            if chunk_embeddings.len() != chunk.len() {
+                return Err(Error::new_message(format!(
+                    "expected {} embeddings in response body, found {}",
+                    chunk.len(),
+                    chunk_embeddings.len()
+                )));
+            }
+            for embedding in chunk_embeddings {
+                embeddings.push(self.postprocess(embedding)?);
+            }
+        }
+        Ok(embeddings)
+    }
+
+    /// The client's configuration as JSON, for the `options` column of `rembed_clients`.
+    /// The API key itself is never included, only whether one is set.
+    pub fn describe(&self) -> serde_json::Value {
+        let retry = self.transport.as_ref().map(|transport| {
+            serde_json::json!({
+                "max_retries": transport.retry.max_retries,
+                "base_delay_ms": transport.retry.base_delay.as_millis() as u64,
+                "max_delay_ms": transport.retry.max_delay.as_millis() as u64,
+                "jitter": transport.retry.jitter,
+            })
+        });
+        let limiter = self
+            .transport
+            .as_ref()
+            .and_then(|transport| transport.limiter.as_ref());
+        serde_json::json!({
+            "format": self.client.format(),
+            "model": self.client.model(),
+            "url": self.client.url(),
+            "key_set": self.client.key_set(),
+            "input_type": self.input_type,
+            "dimensions": self.dimensions,
+            "normalize": self.normalize,
+            "expected_dimensions": self.expected_dimensions,
+            "vector_type": self.vector_type.name(),
+            "parameters": self.client.parameters(),
+            "retry": retry,
+            "rpm": limiter.and_then(|limiter| limiter.rpm()),
+            "tpm": limiter.and_then(|limiter| limiter.tpm()),
+            "cache": self.cache.is_some(),
+        })
+    }
+}
+
+/// The clients registered on a connection, keyed by name. Each client keeps the rowid it
+/// was given in `rembed_clients` until it is deleted.
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: HashMap<String, RegisteredClient>,
+    rowids: BTreeMap<i64, String>,
+    /// The persistent `rembed_clients` table each client was registered from. Clients of
+    /// `temp.rembed_clients` have none.
+    sources: HashMap<String, String>,
+    /// Why each stored client that couldn't be built is unavailable, keyed by name.
+    unavailable: HashMap<String, String>,
+    cache_table: CacheTable,
+}
+
+/// The clients a persistent `rembed_clients` table registered, including the ones that
+/// couldn't be built, as returned by [`ClientRegistry::registered_from`].
+pub type RegisteredFrom = Vec<(String, std::result::Result<RegisteredClient, String>)>;
+
+impl ClientRegistry {
+    /// The connection's `rembed_cache` table, shared by every client with a cache.
+    pub fn cache_table(&self) -> &CacheTable {
+        &self.cache_table
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredClient> {
+        self.clients.get(name)
+    }
+
+    /// The client registered as `name`, or an error saying why it isn't available.
+    pub fn lookup(&self, name: &str) -> Result<&RegisteredClient> {
+        self.clients.get(name).ok_or_else(|| {
+            Error::new_message(match self.unavailable.get(name) {
+                Some(error) => error.clone(),
+                None => format!("Client with name {name} was not registered with rembed_clients."),
+            })
+        })
+    }
+
+    /// Whether `name` is taken, by a client or a stored client that couldn't be built.
+    pub fn contains(&self, name: &str) -> bool {
+        self.clients.contains_key(name) || self.unavailable.contains_key(name)
+    }
+
+    /// The persistent table that `name` was registered from, if any.
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.sources.get(name).map(|source| source.as_str())
+    }
+
+    pub fn rowid(&self, name: &str) -> Option<i64> {
+        self.rowids
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(rowid, _)| *rowid)
+    }
+
+    pub fn name(&self, rowid: i64) -> Option<&str> {
+        self.rowids.get(&rowid).map(|name| name.as_str())
+    }
+
+    /// Registers `client` under `name`, replacing any existing client with that name.
+    /// Returns the client's rowid.
+    pub fn insert(&mut self, name: &str, client: RegisteredClient) -> i64 {
+        self.sources.remove(name);
+        self.unavailable.remove(name);
+        let rowid = self.rowid(name).unwrap_or_else(|| {
+            let rowid = self
+                .rowids
+                .last_key_value()
+                .map(|(rowid, _)| rowid + 1)
+                .unwrap_or(1);
+            self.rowids.insert(rowid, name.to_owned());
+            rowid
+        });
+        self.clients.insert(name.to_owned(), client);
+        rowid
+    }
+
+    /// Registers a client stored in the persistent table `source`, replacing any existing
+    /// client with that name. A client that couldn't be built is registered with the
+    /// error, which [`lookup`](Self::lookup) then reports.
+    pub fn insert_from(
+        &mut self,
+        source: &str,
+        name: &str,
+        client: std::result::Result<RegisteredClient, String>,
+    ) {
+        match client {
+            Ok(client) => {
+                self.insert(name, client);
+            }
+            Err(error) => {
+                if let Some(rowid) = self.rowid(name) {
+                    self.remove(rowid);
+                }
+                self.unavailable.insert(name.to_owned(), error);
+            }
+        }
+        self.sources.insert(name.to_owned(), source.to_owned());
+    }
+
+    /// Replaces the client at `rowid`, renaming it to `name` if needed.
+    pub fn replace(&mut self, rowid: i64, name: &str, client: RegisteredClient) -> Result<()> {
+        let old_name = self
+            .name(rowid)
+            .ok_or_else(|| Error::new_message(format!("No rembed client with rowid {rowid}")))?
+            .to_owned();
+        if old_name != name {
+            if self.contains(name) {
+                return Err(Error::new_message(format!(
+                    "A client with name {name} is already registered with rembed_clients."
+                )));
+            }
+            self.clients.remove(&old_name);
+            self.rowids.insert(rowid, name.to_owned());
+        }
+        self.sources.remove(&old_name);
+        self.clients.insert(name.to_owned(), client);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, rowid: i64) -> Option<(String, RegisteredClient)> {
+        let name = self.rowids.remove(&rowid)?;
+        self.sources.remove(&name);
+        let client = self.clients.remove(&name)?;
+        Some((name, client))
+    }
+
+    /// Unregisters `name` if it was registered from the persistent table `source`.
+    pub fn remove_from(&mut self, source: &str, name: &str) {
+        if self.source(name) != Some(source) {
+            return;
+        }
+        if let Some(rowid) = self.rowid(name) {
+            self.remove(rowid);
+        }
+        self.sources.remove(name);
+        self.unavailable.remove(name);
+    }
+
+    /// Every client registered from the persistent table `source`.
+    pub fn registered_from(&self, source: &str) -> RegisteredFrom {
+        self.sources
+            .iter()
+            .filter(|(_, s)| s.as_str() == source)
+            .map(|(name, _)| {
+                let client = match self.clients.get(name) {
+                    Some(client) => Ok(client.clone()),
+                    None => Err(self.unavailable.get(name).cloned().unwrap_or_default()),
+                };
+                (name.clone(), client)
+            })
+            .collect()
+    }
+
+    /// Puts back the clients that [`registered_from`](Self::registered_from) returned for
+    /// `source`, unregistering any it has registered since. Names that were taken since by
+    /// other clients are skipped.
+    pub fn restore_from(&mut self, source: &str, registered: RegisteredFrom) {
+        let names: Vec<String> = self
+            .sources
+            .iter()
+            .filter(|(_, s)| s.as_str() == source)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.remove_from(source, &name);
+        }
+        for (name, client) in registered {
+            if !self.contains(&name) {
+                self.insert_from(source, &name, client);
+            }
+        }
+    }
+
+    /// Every registered `(rowid, name)` pair, in rowid order.
+    pub fn rows(&self) -> Vec<(i64, String)> {
+        self.rowids
+            .iter()
+            .map(|(rowid, name)| (*rowid, name.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,77 @@
+use sqlite_loadable::Result;
+
+use super::{parse_embeddings_array, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_URL: &str = "https://api-atlas.nomic.ai/v1/embedding/text";
+const DEFAULT_API_KEY_ENV: &str = "NOMIC_API_KEY";
+
+/// [Nomic text embeddings](https://docs.nomic.ai/reference/endpoints/nomic-embed-text).
+pub struct NomicClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `dimensions` option, sent as `dimensionality`.
+    dimensions: Option<usize>,
+}
+
+impl NomicClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(DEFAULT_URL, Some(DEFAULT_API_KEY_ENV))?,
+            dimensions: config.parse_option("dimensions")?,
+        })
+    }
+}
+
+impl EmbeddingClient for NomicClient {
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert("texts".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+
+        if let Some(input_type) = input_type {
+            body.insert("input_type".to_owned(), input_type.to_owned().into());
+        }
+        if let Some(dimensions) = self.dimensions {
+            body.insert("dimensionality".to_owned(), dimensions.into());
+        }
+
+        parse_embeddings_array(self.endpoint.post(body.into(), inputs)?)
+    }
+
+    fn supports_input_type(&self) -> bool {
+        true
+    }
+
+    fn max_batch_size(&self) -> usize {
+        400
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(dimensions) = self.dimensions {
+            parameters.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "nomic"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
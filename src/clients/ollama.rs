@@ -0,0 +1,164 @@
+use sqlite_loadable::{Error, Result};
+use std::cell::Cell;
+
+use super::{parse_embedding, parse_embeddings_array, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::vector::normalize;
+
+const DEFAULT_URL: &str = "http://localhost:11434/api/embed";
+const EMBED_PATH: &str = "/api/embed";
+const LEGACY_PATH: &str = "/api/embeddings";
+
+/// A local [Ollama](https://github.com/ollama/ollama/blob/main/docs/api.md#generate-embeddings)
+/// server. Batches are sent to the `/api/embed` endpoint, falling back to one request per
+/// input on the legacy `/api/embeddings` endpoint for servers older than v0.3.
+pub struct OllamaClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `/api/embeddings` URL to fall back to, or the only URL when `url` points at it.
+    legacy_url: Option<String>,
+    /// Whether requests go to `legacy_url`: always when `url` points at it, or once the
+    /// server turned out not to have `/api/embed`.
+    legacy: Cell<bool>,
+    truncate: Option<bool>,
+    keep_alive: Option<serde_json::Value>,
+    dimensions: Option<usize>,
+}
+
+impl OllamaClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let endpoint = config.endpoint(DEFAULT_URL, None)?;
+        let (legacy_url, legacy) = if endpoint.url.ends_with(LEGACY_PATH) {
+            (Some(endpoint.url.clone()), true)
+        } else if let Some(base) = endpoint.url.strip_suffix(EMBED_PATH) {
+            (Some(format!("{base}{LEGACY_PATH}")), false)
+        } else {
+            (None, false)
+        };
+        // Durations like "10m" are passed as strings, and a number of seconds as a number
+        let keep_alive = config.option("keep_alive").map(|keep_alive| {
+            keep_alive
+                .parse::<i64>()
+                .map(serde_json::Value::from)
+                .unwrap_or_else(|_| keep_alive.into())
+        });
+        Ok(Self {
+            model: config.model()?,
+            endpoint,
+            legacy_url,
+            legacy: Cell::new(legacy),
+            truncate: config.bool_option("truncate")?,
+            keep_alive,
+            dimensions: config.parse_option("dimensions")?,
+        })
+    }
+
+    /// Embeds each input with its own request to the legacy endpoint, which doesn't support
+    /// `truncate` or `dimensions`. After a fallback, embeddings are normalized like the ones
+    /// from `/api/embed`, which share their cache entries.
+    fn infer_legacy(&self, url: &str, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+        inputs
+            .iter()
+            .map(|input| {
+                let mut body = serde_json::Map::new();
+                body.insert("prompt".to_owned(), (*input).into());
+                body.insert("model".to_owned(), self.model.to_owned().into());
+                if let Some(keep_alive) = &self.keep_alive {
+                    body.insert("keep_alive".to_owned(), keep_alive.clone());
+                }
+                let request = self.endpoint.authorized_request(url);
+                let data = self.endpoint.send(request, body.into(), &[input])?;
+                let mut embedding = parse_embedding(
+                    data.get("embedding").unwrap_or(&serde_json::Value::Null),
+                    "embedding",
+                )?;
+                if self.legacy_url.as_ref() != Some(&self.endpoint.url) {
+                    normalize(&mut embedding);
+                }
+                Ok(embedding)
+            })
+            .collect()
+    }
+}
+
+impl EmbeddingClient for OllamaClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        if let (true, Some(url)) = (self.legacy.get(), &self.legacy_url) {
+            return self.infer_legacy(url, inputs);
+        }
+
+        let mut body = self.parameters();
+        body.insert("input".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+        if let Some(keep_alive) = &self.keep_alive {
+            body.insert("keep_alive".to_owned(), keep_alive.clone());
+        }
+
+        let request = self.endpoint.authorized_request(&self.endpoint.url);
+        let fallback = self
+            .legacy_url
+            .as_ref()
+            .map(|_| (404, is_missing_route as fn(&str) -> bool));
+        match self
+            .endpoint
+            .send_unless_status(request, body.into(), inputs, fallback)?
+        {
+            Some(data) => parse_embeddings_array(data),
+            None => {
+                let url = self.legacy_url.as_deref().ok_or_else(|| {
+                    Error::new_message("Ollama server doesn't support /api/embed")
+                })?;
+                let embeddings = self.infer_legacy(url, inputs)?;
+                // only once the legacy endpoint works, so a failed request doesn't switch
+                self.legacy.set(true);
+                Ok(embeddings)
+            }
+        }
+    }
+
+    fn max_batch_size(&self) -> usize {
+        256
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(truncate) = self.truncate {
+            parameters.insert("truncate".to_owned(), truncate.into());
+        }
+        if let Some(dimensions) = self.dimensions {
+            parameters.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    /// The configured endpoint, even after falling back to the legacy one, so the cache
+    /// fingerprint doesn't change mid-session.
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
+
+/// Whether a 404 from `/api/embed` means the server doesn't have the endpoint, like the
+/// plain `404 page not found` of servers older than v0.3. Newer servers answer unknown
+/// models with a JSON `error`, which is reported instead of falling back.
+fn is_missing_route(body: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|body| body.get("error").cloned())
+        .is_none()
+}
@@ -0,0 +1,138 @@
+use sqlite_loadable::Result;
+
+use super::{parse_data_embeddings, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+/// A provider that speaks the OpenAI embeddings protocol, usable as a `format` of its own.
+struct Preset {
+    format: &'static str,
+    url: &'static str,
+    key_env: Option<&'static str>,
+    /// Whether the provider accepts a `dimensions` parameter. Otherwise the `dimensions`
+    /// option is applied locally.
+    dimensions: bool,
+}
+
+const PRESETS: &[Preset] = &[
+    Preset {
+        format: "openai",
+        url: "https://api.openai.com/v1/embeddings",
+        key_env: Some("OPENAI_API_KEY"),
+        dimensions: true,
+    },
+    Preset {
+        format: "mistral",
+        url: "https://api.mistral.ai/v1/embeddings",
+        key_env: Some("MISTRAL_API_KEY"),
+        dimensions: false,
+    },
+    Preset {
+        format: "together",
+        url: "https://api.together.xyz/v1/embeddings",
+        key_env: Some("TOGETHER_API_KEY"),
+        dimensions: false,
+    },
+    Preset {
+        format: "fireworks",
+        url: "https://api.fireworks.ai/inference/v1/embeddings",
+        key_env: Some("FIREWORKS_API_KEY"),
+        dimensions: false,
+    },
+    Preset {
+        format: "deepinfra",
+        url: "https://api.deepinfra.com/v1/openai/embeddings",
+        key_env: Some("DEEPINFRA_API_KEY"),
+        dimensions: false,
+    },
+    Preset {
+        format: "lmstudio",
+        url: "http://localhost:1234/v1/embeddings",
+        key_env: None,
+        dimensions: false,
+    },
+    Preset {
+        format: "vllm",
+        url: "http://localhost:8000/v1/embeddings",
+        key_env: None,
+        dimensions: true,
+    },
+];
+
+/// [OpenAI embeddings](https://platform.openai.com/docs/guides/embeddings), or any provider in
+/// [`PRESETS`] that uses the same protocol with a different default URL and API key.
+pub struct OpenAiClient {
+    format: &'static str,
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `dimensions` option, for presets that send it to the provider.
+    dimensions: Option<usize>,
+}
+
+impl OpenAiClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Self::with_preset(&PRESETS[0], config)
+    }
+
+    /// The client for the preset named `format`, if there is one.
+    pub(crate) fn from_preset(format: &str, config: &ClientConfig) -> Option<Result<Self>> {
+        PRESETS
+            .iter()
+            .find(|preset| preset.format == format)
+            .map(|preset| Self::with_preset(preset, config))
+    }
+
+    fn with_preset(preset: &Preset, config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            format: preset.format,
+            model: config.model()?,
+            endpoint: config.endpoint(preset.url, preset.key_env)?,
+            dimensions: match preset.dimensions {
+                true => config.parse_option("dimensions")?,
+                false => None,
+            },
+        })
+    }
+}
+
+impl EmbeddingClient for OpenAiClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert("input".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+        if let Some(dimensions) = self.dimensions {
+            body.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parse_data_embeddings(self.endpoint.post(body.into(), inputs)?)
+    }
+
+    fn max_batch_size(&self) -> usize {
+        2048
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(dimensions) = self.dimensions {
+            parameters.insert("dimensions".to_owned(), dimensions.into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        self.format
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
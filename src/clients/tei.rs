@@ -0,0 +1,78 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{parse_embedding, ClientConfig, EmbeddingClient, HttpEndpoint};
+
+const DEFAULT_URL: &str = "http://localhost:8080/embed";
+
+/// [Hugging Face Text Embeddings Inference](https://huggingface.github.io/text-embeddings-inference/).
+/// The server hosts a single model, so no model is sent. An API key is only sent when given,
+/// for servers behind Inference Endpoints.
+pub struct TeiClient {
+    model: Option<String>,
+    endpoint: HttpEndpoint,
+    normalize: Option<bool>,
+    truncate: Option<bool>,
+    prompt_name: Option<String>,
+}
+
+impl TeiClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model.clone(),
+            endpoint: config.endpoint(DEFAULT_URL, None)?,
+            normalize: config.bool_option("normalize")?,
+            truncate: config.bool_option("truncate")?,
+            prompt_name: config.option("prompt_name").map(|name| name.to_owned()),
+        })
+    }
+}
+
+impl EmbeddingClient for TeiClient {
+    fn infer_batch(&self, inputs: &[&str], _input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = self.parameters();
+        body.insert("inputs".to_owned(), inputs.into());
+
+        self.endpoint
+            .post(body.into(), inputs)?
+            .as_array()
+            .ok_or_else(|| Error::new_message("expected response body to be an array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, embedding)| parse_embedding(embedding, &i.to_string()))
+            .collect()
+    }
+
+    fn max_batch_size(&self) -> usize {
+        32
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(normalize) = self.normalize {
+            parameters.insert("normalize".to_owned(), normalize.into());
+        }
+        if let Some(truncate) = self.truncate {
+            parameters.insert("truncate".to_owned(), truncate.into());
+        }
+        if let Some(prompt_name) = &self.prompt_name {
+            parameters.insert("prompt_name".to_owned(), prompt_name.to_owned().into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "tei"
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
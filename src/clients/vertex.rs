@@ -0,0 +1,151 @@
+use sqlite_loadable::{Error, Result};
+
+use super::{gemini::task_type, parse_embedding, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::oauth::{ServiceAccount, TokenSource};
+
+const CREDENTIALS_ENV: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+const DEFAULT_LOCATION: &str = "us-central1";
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// [Vertex AI text embeddings](https://cloud.google.com/vertex-ai/generative-ai/docs/model-reference/text-embeddings-api),
+/// authenticated with access tokens minted from a service account key.
+pub struct VertexClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    tokens: TokenSource,
+    output_dimensionality: Option<usize>,
+}
+
+impl VertexClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        let model = config.model()?;
+        let credentials = match config.option("credentials") {
+            Some(path) => path.to_owned(),
+            None => std::env::var(CREDENTIALS_ENV).map_err(|_| {
+                Error::new_message(format!(
+                    "{CREDENTIALS_ENV} environment variable not defined. Alternatively, pass in a service account key file with the 'credentials' option"
+                ))
+            })?,
+        };
+        let account = ServiceAccount::from_file(&credentials)?;
+        let project = config
+            .option("project")
+            .map(|project| project.to_owned())
+            .or_else(|| account.project_id.clone())
+            .ok_or_else(|| {
+                Error::new_message("'project' option is required for the 'vertex' format")
+            })?;
+        let location = config.option("location").unwrap_or(DEFAULT_LOCATION);
+        let default_url = format!(
+            "https://{location}-aiplatform.googleapis.com/v1/projects/{project}/locations/{location}/publishers/google/models/{model}:predict"
+        );
+        let mut endpoint = config.endpoint(&default_url, None)?;
+        endpoint.key = None;
+
+        Ok(Self {
+            model,
+            endpoint,
+            tokens: TokenSource::new(
+                account,
+                config.option("token_url").map(|url| url.to_owned()),
+                SCOPE,
+            ),
+            output_dimensionality: config.parse_option("output_dimensionality")?,
+        })
+    }
+}
+
+impl EmbeddingClient for VertexClient {
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let task_type = input_type.map(task_type).transpose()?;
+        let instances: Vec<serde_json::Value> = inputs
+            .iter()
+            .map(|input| {
+                let mut instance = serde_json::Map::new();
+                instance.insert("content".to_owned(), (*input).into());
+                if let Some(task_type) = task_type {
+                    instance.insert("task_type".to_owned(), task_type.into());
+                }
+                instance.into()
+            })
+            .collect();
+        let mut body = serde_json::Map::new();
+        body.insert("instances".to_owned(), instances.into());
+        if let Some(output_dimensionality) = self.output_dimensionality {
+            body.insert(
+                "parameters".to_owned(),
+                serde_json::json!({ "outputDimensionality": output_dimensionality }),
+            );
+        }
+
+        let token = self.tokens.token()?;
+        let request = self
+            .endpoint
+            .request(&self.endpoint.url)
+            .set("Authorization", &format!("Bearer {token}"));
+        self.endpoint
+            .send(request, body.into(), inputs)?
+            .get("predictions")
+            .ok_or_else(|| Error::new_message("expected 'predictions' key in response body"))?
+            .as_array()
+            .ok_or_else(|| Error::new_message("expected 'predictions' path to be an array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, prediction)| {
+                let path = format!("predictions.{i}.embeddings.values");
+                let values = prediction
+                    .get("embeddings")
+                    .and_then(|embeddings| embeddings.get("values"))
+                    .ok_or_else(|| {
+                        Error::new_message(format!("expected '{path}' path in response body"))
+                    })?;
+                parse_embedding(values, &path)
+            })
+            .collect()
+    }
+
+    fn supports_input_type(&self) -> bool {
+        true
+    }
+
+    /// `gemini-embedding` models only accept one instance per request.
+    fn max_batch_size(&self) -> usize {
+        if self.model.starts_with("gemini-embedding") {
+            1
+        } else {
+            250
+        }
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.output_dimensionality
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(output_dimensionality) = self.output_dimensionality {
+            parameters.insert(
+                "output_dimensionality".to_owned(),
+                output_dimensionality.into(),
+            );
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "vertex"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    /// Requests are always sent with an access token for the service account.
+    fn key_set(&self) -> bool {
+        true
+    }
+}
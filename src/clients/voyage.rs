@@ -0,0 +1,105 @@
+use sqlite_loadable::Result;
+
+use super::{parse_data_embeddings, ClientConfig, EmbeddingClient, HttpEndpoint};
+use crate::vector::{unpack_provider_bits, VectorType};
+
+const DEFAULT_URL: &str = "https://api.voyageai.com/v1/embeddings";
+const DEFAULT_API_KEY_ENV: &str = "VOYAGE_API_KEY";
+const OUTPUT_DTYPES: &[&str] = &["float", "int8", "uint8", "binary", "ubinary"];
+
+/// [Voyage AI embeddings](https://docs.voyageai.com/reference/embeddings-api).
+pub struct VoyageClient {
+    model: String,
+    endpoint: HttpEndpoint,
+    /// The `output_dimension` option, for models that support several dimensions.
+    output_dimension: Option<usize>,
+    /// The `output_dtype` option, or else the one matching the `output` option. `uint8`
+    /// values are returned as floats, as `sqlite-vec` has no unsigned vectors.
+    output_dtype: Option<String>,
+}
+
+impl VoyageClient {
+    pub(crate) fn new(config: &ClientConfig) -> Result<Self> {
+        Ok(Self {
+            model: config.model()?,
+            endpoint: config.endpoint(DEFAULT_URL, Some(DEFAULT_API_KEY_ENV))?,
+            output_dimension: config.parse_option("output_dimension")?,
+            output_dtype: match config.choice_option("output_dtype", OUTPUT_DTYPES)? {
+                Some(output_dtype) => Some(output_dtype),
+                None => match config.output {
+                    Some(VectorType::Int8) => Some("int8".to_owned()),
+                    Some(VectorType::Bit) => Some("ubinary".to_owned()),
+                    _ => None,
+                },
+            },
+        })
+    }
+}
+
+impl EmbeddingClient for VoyageClient {
+    fn infer_batch(&self, inputs: &[&str], input_type: Option<&str>) -> Result<Vec<Vec<f32>>> {
+        let mut body = serde_json::Map::new();
+        body.insert("input".to_owned(), inputs.into());
+        body.insert("model".to_owned(), self.model.to_owned().into());
+
+        if let Some(input_type) = input_type {
+            body.insert("input_type".to_owned(), input_type.to_owned().into());
+        }
+        body.extend(self.parameters());
+
+        let mut embeddings = parse_data_embeddings(self.endpoint.post(body.into(), inputs)?)?;
+        if let Some(output_dtype @ ("binary" | "ubinary")) = self.output_dtype.as_deref() {
+            for embedding in &mut embeddings {
+                unpack_provider_bits(embedding, output_dtype == "binary");
+            }
+        }
+        Ok(embeddings)
+    }
+
+    fn supports_input_type(&self) -> bool {
+        true
+    }
+
+    fn max_batch_size(&self) -> usize {
+        1000
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.output_dimension
+    }
+
+    fn vector_type(&self) -> VectorType {
+        match self.output_dtype.as_deref() {
+            Some("int8") => VectorType::Int8,
+            Some("binary" | "ubinary") => VectorType::Bit,
+            _ => VectorType::Float32,
+        }
+    }
+
+    fn parameters(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut parameters = serde_json::Map::new();
+        if let Some(output_dimension) = self.output_dimension {
+            parameters.insert("output_dimension".to_owned(), output_dimension.into());
+        }
+        if let Some(output_dtype) = &self.output_dtype {
+            parameters.insert("output_dtype".to_owned(), output_dtype.to_owned().into());
+        }
+        parameters
+    }
+
+    fn format(&self) -> &str {
+        "voyage"
+    }
+
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.endpoint.url)
+    }
+
+    fn key_set(&self) -> bool {
+        self.endpoint.key_set()
+    }
+}
@@ -1,184 +1,880 @@
-use sqlite_loadable::table::UpdateOperation;
-use sqlite_loadable::{api, prelude::*, Error};
-use sqlite_loadable::{
-    api::ValueType,
-    table::{IndexInfo, VTab, VTabArguments, VTabCursor, VTabWriteable},
-    BestIndexError, Result,
+//! The `rembed_clients` virtual table.
+//!
+//! sqlite-loadable's writeable virtual tables can't handle UPDATE statements yet, and drop
+//! the error message of any failed write, so this table is wired up to SQLite directly.
+//!
+//! The eponymous `temp.rembed_clients` table only lives as long as the connection. Tables
+//! created with `CREATE VIRTUAL TABLE x USING rembed_clients()` also store their client
+//! definitions in an `x_clients` shadow table, and register them again on every new
+//! connection.
+
+use sqlite_loadable::ext::{
+    sqlite3_index_info, sqlite3_module, sqlite3ext_create_module_v2, sqlite3ext_declare_vtab,
+};
+use sqlite_loadable::{api, prelude::*, Error, ErrorKind};
+use sqlite_loadable::{api::ValueType, Result, SQLITE_OKAY};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_void, CStr, CString},
+    mem,
+    os::raw::c_int,
+    rc::Rc,
+    slice,
 };
-use std::{cell::RefCell, collections::HashMap, marker::PhantomData, mem, os::raw::c_int, rc::Rc};
 
-use crate::clients::MixedbreadClient;
+use crate::sql::{execute, quote_identifier, Statement};
 use crate::{
-    clients::{
-        Client, CohereClient, JinaClient, LlamafileClient, NomicClient, OllamaClient, OpenAiClient,
-    },
+    clients::{ClientRegistry, RegisteredClient, RegisteredFrom, SECRET_OPTIONS},
     CLIENT_OPTIONS_POINTER_NAME,
 };
 
+const MODULE_NAME: &str = "rembed_clients";
+/// Suffix of the shadow table that persistent tables store their clients in.
+const SHADOW_SUFFIX: &str = "clients";
+
+const SQLITE_INDEX_CONSTRAINT_EQ: u8 = 2;
+const SQLITE_INDEX_SCAN_UNIQUE: c_int = 1;
+
+/// The `idxNum` of a scan, telling [`x_filter`] which rows to return.
+const FULL_SCAN: c_int = 1;
+/// A lookup by `rowid = ?`.
+const ROWID_LOOKUP: c_int = 2;
+/// A lookup by `name = ?`.
+const NAME_LOOKUP: c_int = 3;
+
 enum Columns {
     Name,
     Options,
+    Format,
+    Model,
+    Url,
 }
 fn column(index: i32) -> Option<Columns> {
     match index {
         0 => Some(Columns::Name),
         1 => Some(Columns::Options),
+        2 => Some(Columns::Format),
+        3 => Some(Columns::Model),
+        4 => Some(Columns::Url),
         _ => None,
     }
 }
+
+/// The client for a format name, using the client's name as the model.
+fn client_from_format(name: &str, format: &str) -> Result<RegisteredClient> {
+    RegisteredClient::from_options(HashMap::from([
+        ("format".to_owned(), format.to_owned()),
+        ("model".to_owned(), name.to_owned()),
+    ]))
+}
+
+/// The client described by an `options` column value: either the name of a format, or the
+/// result of `rembed_client_options()`. Returns `None` for a plain NULL.
+fn client_from_options(
+    name: &str,
+    options: *mut sqlite3_value,
+) -> Result<Option<RegisteredClient>> {
+    let client = match api::value_type(&options) {
+        ValueType::Text => client_from_format(name, api::value_text(&options)?)?,
+        ValueType::Null => unsafe {
+            match api::value_pointer::<RegisteredClient>(&options, CLIENT_OPTIONS_POINTER_NAME) {
+                Some(client) => (*client).clone(),
+                None => return Ok(None),
+            }
+        },
+        _ => return Err(Error::new_message("client options required")),
+    };
+    Ok(Some(client))
+}
+
+/// How a persistent table stores a client: the format name as given, or its
+/// `rembed_client_options()` pairs as a JSON object. API keys are never stored, nor are
+/// custom headers that could hold one instead of using the `{{key}}` placeholder.
+fn stored_options(
+    table: &str,
+    options: *mut sqlite3_value,
+    client: &RegisteredClient,
+) -> Result<String> {
+    if api::value_type(&options) == ValueType::Text {
+        return Ok(api::value_text(&options)?.to_owned());
+    }
+    if let Some(secret) = SECRET_OPTIONS
+        .iter()
+        .find(|secret| client.definition.contains_key(**secret))
+    {
+        return Err(Error::new_message(format!(
+            "API keys are not stored in {table}. Use the '{secret}_env' option to read the {secret} from an environment variable instead."
+        )));
+    }
+    if let Some(header) = client
+        .definition
+        .iter()
+        .find(|(option, value)| option.starts_with("header:") && !value.contains("{{key}}"))
+        .map(|(option, _)| option)
+    {
+        return Err(Error::new_message(format!(
+            "Custom headers are not stored in {table} unless they send the API key with {{{{key}}}}, as '{header}' could hold a secret. Use the 'key_env' option to read the key from an environment variable instead."
+        )));
+    }
+    serde_json::to_string(&client.definition)
+        .map_err(|error| Error::new_message(format!("Error serializing client options: {error}")))
+}
+
+/// The error reported for a stored client that can't be rebuilt.
+fn restore_error(table: &str, name: &str, error: &Error) -> String {
+    format!(
+        "Error restoring client {name} from {table}: {}",
+        error_message(error)
+    )
+}
+
+/// Rebuilds a client from its stored options, see [`stored_options`].
+fn restore_client(name: &str, stored: &str) -> Result<RegisteredClient> {
+    if !stored.starts_with('{') {
+        return client_from_format(name, stored);
+    }
+    let options: HashMap<String, String> = serde_json::from_str(stored).map_err(|error| {
+        Error::new_message(format!("Invalid stored options for client {name}: {error}"))
+    })?;
+    RegisteredClient::from_options(options)
+}
+
+/// The shadow table of a persistent `rembed_clients` table.
+struct Storage {
+    db: *mut sqlite3,
+    /// Display name of the virtual table, for error messages.
+    name: String,
+    /// Quoted, schema-qualified name of the shadow table.
+    table: String,
+}
+
+impl Storage {
+    /// Opens the shadow table of `schema.name`, creating it if needed.
+    fn open(db: *mut sqlite3, schema: &str, name: &str) -> Result<Self> {
+        let shadow_name = format!("{name}_{SHADOW_SUFFIX}");
+        let table = format!(
+            "{}.{}",
+            quote_identifier(schema),
+            quote_identifier(&shadow_name)
+        );
+        let mut stmt = Statement::prepare(
+            db,
+            &format!(
+                "select 1 from {}.sqlite_master where type = 'table' and name = ?",
+                quote_identifier(schema)
+            ),
+        )?;
+        stmt.bind_text(1, &shadow_name)?;
+        if !stmt.step()? {
+            execute(
+                db,
+                &format!(
+                    "create table {table}(id integer primary key, name text unique not null, options text not null)"
+                ),
+            )?;
+        }
+        Ok(Self {
+            db,
+            name: format!("{schema}.{name}"),
+            table,
+        })
+    }
+
+    /// Every stored `(id, name, options)` row, in id order.
+    fn rows(&self) -> Result<Vec<(i64, String, String)>> {
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!("select id, name, options from {} order by id", self.table),
+        )?;
+        let mut rows = vec![];
+        while stmt.step()? {
+            rows.push((
+                api::value_int64(&stmt.column_value(0)),
+                api::value_text(&stmt.column_value(1))?.to_owned(),
+                api::value_text(&stmt.column_value(2))?.to_owned(),
+            ));
+        }
+        Ok(rows)
+    }
+
+    fn options(&self, id: i64) -> Result<Option<String>> {
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!("select options from {} where id = ?", self.table),
+        )?;
+        stmt.bind_int64(1, id)?;
+        if !stmt.step()? {
+            return Ok(None);
+        }
+        Ok(Some(api::value_text(&stmt.column_value(0))?.to_owned()))
+    }
+
+    fn name(&self, id: i64) -> Result<Option<String>> {
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!("select name from {} where id = ?", self.table),
+        )?;
+        stmt.bind_int64(1, id)?;
+        if !stmt.step()? {
+            return Ok(None);
+        }
+        Ok(Some(api::value_text(&stmt.column_value(0))?.to_owned()))
+    }
+
+    /// Stores a client, replacing the options of any client with the same name. Returns
+    /// the client's id.
+    fn insert(&self, name: &str, options: &str) -> Result<i64> {
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!("select id from {} where name = ?", self.table),
+        )?;
+        stmt.bind_text(1, name)?;
+        let id = if stmt.step()? {
+            api::value_int64(&stmt.column_value(0))
+        } else {
+            let mut stmt = Statement::prepare(
+                self.db,
+                &format!("select coalesce(max(id), 0) + 1 from {}", self.table),
+            )?;
+            stmt.step()?;
+            api::value_int64(&stmt.column_value(0))
+        };
+        drop(stmt);
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!(
+                "insert or replace into {}(id, name, options) values (?, ?, ?)",
+                self.table
+            ),
+        )?;
+        stmt.bind_int64(1, id)?;
+        stmt.bind_text(2, name)?;
+        stmt.bind_text(3, options)?;
+        while stmt.step()? {}
+        Ok(id)
+    }
+
+    /// Renames the client at `id`, and replaces its options when given.
+    fn update(&self, id: i64, name: &str, options: Option<&str>) -> Result<()> {
+        let mut stmt = Statement::prepare(
+            self.db,
+            &format!(
+                "update {} set name = ?, options = coalesce(?, options) where id = ?",
+                self.table
+            ),
+        )?;
+        stmt.bind_text(1, name)?;
+        if let Some(options) = options {
+            stmt.bind_text(2, options)?;
+        }
+        stmt.bind_int64(3, id)?;
+        while stmt.step()? {}
+        Ok(())
+    }
+
+    fn delete(&self, id: i64) -> Result<()> {
+        let mut stmt =
+            Statement::prepare(self.db, &format!("delete from {} where id = ?", self.table))?;
+        stmt.bind_int64(1, id)?;
+        while stmt.step()? {}
+        Ok(())
+    }
+
+    fn drop_table(&self) -> Result<()> {
+        execute(self.db, &format!("drop table {}", self.table))
+    }
+}
+
 #[repr(C)]
 pub struct ClientsTable {
     /// must be first
     base: sqlite3_vtab,
-    clients: Rc<RefCell<HashMap<String, Client>>>,
+    clients: Rc<RefCell<ClientRegistry>>,
+    /// Set for persistent tables, `None` for the eponymous `rembed_clients` table.
+    storage: Option<Storage>,
+    /// The clients a persistent table had registered when the transaction (level -1) and
+    /// each savepoint since began. The registry isn't rolled back with the shadow table, so
+    /// they're put back on rollback.
+    snapshots: RefCell<Vec<(c_int, RegisteredFrom)>>,
 }
 
-impl<'vtab> VTab<'vtab> for ClientsTable {
-    type Aux = Rc<RefCell<HashMap<String, Client>>>;
-    type Cursor = ClientsCursor<'vtab>;
-
-    fn create(
-        db: *mut sqlite3,
-        aux: Option<&Self::Aux>,
-        args: VTabArguments,
-    ) -> Result<(String, Self)> {
-        Self::connect(db, aux, args)
+impl ClientsTable {
+    /// Registers every stored client whose name isn't taken yet. Clients that can't be
+    /// built (e.g. because an API key environment variable isn't set) are registered with
+    /// the error, which `rembed()` reports when they're used.
+    fn restore(&self, storage: &Storage) -> Result<()> {
+        for (_, name, options) in storage.rows()? {
+            let mut clients = self.clients.borrow_mut();
+            if clients.contains(&name) {
+                continue;
+            }
+            let client = restore_client(&name, &options)
+                .map_err(|error| restore_error(&storage.name, &name, &error));
+            clients.insert_from(&storage.name, &name, client);
+        }
+        Ok(())
     }
-    fn connect(
-        _db: *mut sqlite3,
-        aux: Option<&Self::Aux>,
-        _args: VTabArguments,
-    ) -> Result<(String, ClientsTable)> {
-        let base: sqlite3_vtab = unsafe { mem::zeroed() };
-        let clients = aux.expect("Required aux").to_owned();
 
-        let vtab = ClientsTable { base, clients };
-        let sql = "create table x(name text primary key, options)".to_owned();
+    /// The client this table shows at `rowid`. When a persistent table's client isn't the
+    /// one registered under its name, like when `temp.rembed_clients` has a client with the
+    /// same name, it's rebuilt from its stored options.
+    fn client(&self, rowid: i64, name: &str) -> Result<RegisteredClient> {
+        let clients = self.clients.borrow();
+        match &self.storage {
+            Some(storage) if clients.source(name) != Some(storage.name.as_str()) => {
+                let options = storage.options(rowid)?.ok_or_else(|| {
+                    Error::new_message(format!("No rembed client with rowid {rowid}"))
+                })?;
+                restore_client(name, &options)
+                    .map_err(|error| Error::new_message(restore_error(&storage.name, name, &error)))
+            }
+            _ => clients.lookup(name).cloned(),
+        }
+    }
 
-        Ok((sql, vtab))
+    /// The `(rowid, name)` pairs shown by this table.
+    fn rows(&self) -> Result<Vec<(i64, String)>> {
+        match &self.storage {
+            Some(storage) => Ok(storage
+                .rows()?
+                .into_iter()
+                .map(|(id, name, _)| (id, name))
+                .collect()),
+            None => Ok(self.clients.borrow().rows()),
+        }
     }
-    fn destroy(&self) -> Result<()> {
-        Ok(())
+
+    fn name(&self, rowid: i64) -> Result<String> {
+        let name = match &self.storage {
+            Some(storage) => storage.name(rowid)?,
+            None => self
+                .clients
+                .borrow()
+                .name(rowid)
+                .map(|name| name.to_owned()),
+        };
+        name.ok_or_else(|| Error::new_message(format!("No rembed client with rowid {rowid}")))
     }
 
-    fn best_index(&self, mut info: IndexInfo) -> core::result::Result<(), BestIndexError> {
-        info.set_estimated_cost(10000.0);
-        info.set_estimated_rows(10000);
-        info.set_idxnum(1);
+    /// Deletes the client at `rowid`. A persistent table only unregisters clients it
+    /// registered itself.
+    fn delete(&self, rowid: i64) -> Result<()> {
+        match &self.storage {
+            Some(storage) => {
+                let name = self.name(rowid)?;
+                storage.delete(rowid)?;
+                self.clients.borrow_mut().remove_from(&storage.name, &name);
+            }
+            None => {
+                self.clients.borrow_mut().remove(rowid).ok_or_else(|| {
+                    Error::new_message(format!("No rembed client with rowid {rowid}"))
+                })?;
+            }
+        }
         Ok(())
     }
 
-    fn open(&'vtab mut self) -> Result<ClientsCursor<'vtab>> {
-        ClientsCursor::new(self)
+    /// Registers a new client. Like when they're restored, a persistent table's clients are
+    /// only registered when their name isn't already taken by another table's client.
+    fn insert(&self, values: &[*mut sqlite3_value]) -> Result<i64> {
+        let name = api::value_text(&values[0])?;
+        let client = client_from_options(name, values[1])?
+            .ok_or_else(|| Error::new_message("client options required"))?;
+        match &self.storage {
+            Some(storage) => {
+                let rowid =
+                    storage.insert(name, &stored_options(&storage.name, values[1], &client)?)?;
+                let mut clients = self.clients.borrow_mut();
+                if !clients.contains(name) || clients.source(name) == Some(storage.name.as_str()) {
+                    clients.insert_from(&storage.name, name, Ok(client));
+                }
+                Ok(rowid)
+            }
+            None => Ok(self.clients.borrow_mut().insert(name, client)),
+        }
     }
-}
 
-impl<'vtab> VTabWriteable<'vtab> for ClientsTable {
-    fn update(&'vtab mut self, operation: UpdateOperation<'_>, _p_rowid: *mut i64) -> Result<()> {
-        match operation {
-            UpdateOperation::Delete(_) => {
-                return Err(Error::new_message(
-                    "DELETE operations on rembed_clients is not supported yet",
-                ))
+    /// Renames and/or replaces the client at `rowid`. The current client is kept when
+    /// `options` still holds the client's description, so `UPDATE rembed_clients SET name
+    /// = ...` only renames it.
+    fn update(&self, rowid: i64, values: &[*mut sqlite3_value]) -> Result<()> {
+        let name = api::value_text(&values[0])?;
+        let current_name = self.name(rowid)?;
+        let current = self.client(rowid, &current_name);
+        let unchanged = match &current {
+            Ok(current) => {
+                api::value_type(&values[1]) == ValueType::Text
+                    && serde_json::from_str::<serde_json::Value>(api::value_text(&values[1])?).ok()
+                        == Some(current.describe())
             }
-            UpdateOperation::Update { _values } => {
-                return Err(Error::new_message(
-                    "DELETE operations on rembed_clients is not supported yet",
-                ))
+            Err(_) => false,
+        };
+        let client = if unchanged {
+            current?
+        } else {
+            match (client_from_options(name, values[1])?, current) {
+                (Some(client), _) => client,
+                (None, Ok(_)) => return Err(Error::new_message(
+                    "client options required. The result of rembed_client_options() is only kept when updating a single client, with a WHERE name = ... or rowid = ... constraint",
+                )),
+                (None, Err(error)) => return Err(Error::new_message(format!(
+                    "{}. Set new options with rembed_client_options(), or delete the client",
+                    error_message(&error)
+                ))),
             }
-            UpdateOperation::Insert { values, rowid: _ } => {
-                let name = api::value_text(&values[0])?;
-                let client = match api::value_type(&values[1]) {
-                    ValueType::Text => match api::value_text(&values[1])? {
-                        "openai" => Client::OpenAI(OpenAiClient::new(name, None, None)?),
-                        "mixedbread" => {
-                            Client::Mixedbread(MixedbreadClient::new(name, None, None)?)
-                        }
-                        "jina" => Client::Jina(JinaClient::new(name, None, None)?),
-                        "nomic" => Client::Nomic(NomicClient::new(name, None, None)?),
-                        "cohere" => Client::Cohere(CohereClient::new(name, None, None)?),
-                        "ollama" => Client::Ollama(OllamaClient::new(name, None)),
-                        "llamafile" => Client::Llamafile(LlamafileClient::new(None)),
-                        text => {
-                            return Err(Error::new_message(format!(
-                                "'{text}' is not a valid rembed client."
-                            )))
-                        }
-                    },
-                    ValueType::Null => unsafe {
-                        if let Some(client) =
-                            api::value_pointer::<Client>(&values[1], CLIENT_OPTIONS_POINTER_NAME)
-                        {
-                            (*client).clone()
-                        } else {
-                            return Err(Error::new_message("client options required"));
-                        }
-                    },
-                    _ => return Err(Error::new_message("client options required")),
+        };
+
+        match &self.storage {
+            Some(storage) => {
+                let stored = match unchanged {
+                    true => None,
+                    false => Some(stored_options(&storage.name, values[1], &client)?),
                 };
-                self.clients.borrow_mut().insert(name.to_owned(), client);
+                storage.update(rowid, name, stored.as_deref())?;
+                let mut clients = self.clients.borrow_mut();
+                clients.remove_from(&storage.name, &current_name);
+                if !clients.contains(name) {
+                    clients.insert_from(&storage.name, name, Ok(client));
+                }
             }
+            None => self.clients.borrow_mut().replace(rowid, name, client)?,
         }
         Ok(())
     }
+
+    /// Drops the shadow table of a persistent table, and unregisters its clients.
+    fn destroy(&self) -> Result<()> {
+        if let Some(storage) = &self.storage {
+            storage.drop_table()?;
+            self.clients
+                .borrow_mut()
+                .restore_from(&storage.name, RegisteredFrom::new());
+        }
+        Ok(())
+    }
+
+    /// Remembers this table's registered clients as of the transaction (level -1) or
+    /// savepoint `level`.
+    fn savepoint(&self, level: c_int) {
+        if let Some(storage) = &self.storage {
+            let registered = self.clients.borrow().registered_from(&storage.name);
+            self.snapshots.borrow_mut().push((level, registered));
+        }
+    }
+
+    /// Puts back this table's registered clients as of savepoint `level`, or as of the
+    /// start of the transaction for level -1. A table that joined the transaction after
+    /// that savepoint began hadn't changed any clients yet, so its earliest later snapshot
+    /// is used.
+    fn rollback_to(&self, level: c_int) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        let mut snapshots = self.snapshots.borrow_mut();
+        if let Some(index) = snapshots.iter().position(|(l, _)| *l >= level) {
+            snapshots.truncate(index + 1);
+            snapshots[index].0 = level;
+            let registered = snapshots[index].1.clone();
+            self.clients
+                .borrow_mut()
+                .restore_from(&storage.name, registered);
+        }
+    }
+
+    /// Forgets the snapshots of savepoint `level` and the ones after it, or every snapshot
+    /// for level -1.
+    fn release(&self, level: c_int) {
+        self.snapshots.borrow_mut().retain(|(l, _)| *l < level);
+    }
 }
 
 #[repr(C)]
-pub struct ClientsCursor<'vtab> {
+pub struct ClientsCursor {
     /// Base class. Must be first
     base: sqlite3_vtab_cursor,
-    keys: Vec<String>,
-    rowid: i64,
-    phantom: PhantomData<&'vtab ClientsTable>,
-}
-impl ClientsCursor<'_> {
-    fn new(table: &mut ClientsTable) -> Result<ClientsCursor> {
-        let base: sqlite3_vtab_cursor = unsafe { mem::zeroed() };
-        let c = table.clients.borrow();
-        let keys = c.keys().map(|k| k.to_string()).collect();
-        let cursor = ClientsCursor {
-            base,
-            keys,
-            rowid: 0,
-            phantom: PhantomData,
-        };
-        Ok(cursor)
+    rows: Vec<(i64, String)>,
+    index: usize,
+}
+
+impl ClientsCursor {
+    fn current(&self) -> &(i64, String) {
+        self.rows
+            .get(self.index)
+            .expect("Internal rembed_clients logic error")
     }
 }
 
-impl VTabCursor for ClientsCursor<'_> {
-    fn filter(
-        &mut self,
-        _idx_num: c_int,
-        _idx_str: Option<&str>,
-        _values: &[*mut sqlite3_value],
-    ) -> Result<()> {
-        Ok(())
+fn error_message(err: &Error) -> String {
+    match err.kind() {
+        ErrorKind::Message(msg) => msg.to_owned(),
+        _ => format!("{err:?}"),
     }
+}
 
-    fn next(&mut self) -> Result<()> {
-        self.rowid += 1;
-        Ok(())
+/// Stores `err`'s message on the table so SQLite reports it, and returns its code.
+unsafe fn set_vtab_error(vtab: *mut sqlite3_vtab, err: Error) -> c_int {
+    if let Ok(msg) = api::mprintf(&error_message(&err)) {
+        (*vtab).zErrMsg = msg;
     }
+    err.code()
+}
 
-    fn eof(&self) -> bool {
-        (self.rowid as usize) >= self.keys.len()
+/// Used as both xCreate and xConnect, which keeps `rembed_clients` eponymous. Persistent
+/// tables are told apart by having a different name than the module.
+unsafe extern "C" fn x_connect(
+    db: *mut sqlite3,
+    p_aux: *mut c_void,
+    argc: c_int,
+    argv: *const *const c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    pz_err: *mut *mut c_char,
+) -> c_int {
+    let clients = &*p_aux.cast::<Rc<RefCell<ClientRegistry>>>();
+    let args: Vec<&str> = slice::from_raw_parts(argv, argc as usize)
+        .iter()
+        .map(|arg| CStr::from_ptr(*arg).to_str().unwrap_or_default())
+        .collect();
+    let sql = CString::new(
+        "create table x(name text primary key, options, format hidden, model hidden, url hidden)",
+    )
+    .unwrap();
+    let rc = sqlite3ext_declare_vtab(db, sql.as_ptr());
+    if rc != SQLITE_OKAY {
+        return rc;
     }
 
-    fn column(&self, context: *mut sqlite3_context, i: c_int) -> Result<()> {
-        let key = self
-            .keys
-            .get(self.rowid as usize)
-            .expect("Internal rembed_clients logic error");
-        match column(i) {
-            Some(Columns::Name) => api::result_text(context, key)?,
-            Some(Columns::Options) => (),
-            None => (),
-        };
-        Ok(())
+    let mut table = ClientsTable {
+        base: mem::zeroed(),
+        clients: Rc::clone(clients),
+        storage: None,
+        snapshots: RefCell::new(vec![]),
+    };
+    if args.len() > 3 {
+        let error = Error::new_message("rembed_clients() doesn't take any arguments");
+        if let Ok(msg) = api::mprintf(&error_message(&error)) {
+            *pz_err = msg;
+        }
+        return error.code();
+    }
+    if args[2] != MODULE_NAME {
+        let result = Storage::open(db, args[1], args[2]).and_then(|storage| {
+            table.restore(&storage)?;
+            Ok(storage)
+        });
+        match result {
+            Ok(storage) => table.storage = Some(storage),
+            Err(error) => {
+                if let Ok(msg) = api::mprintf(&error_message(&error)) {
+                    *pz_err = msg;
+                }
+                return error.code();
+            }
+        }
+    }
+    *pp_vtab = Box::into_raw(Box::new(table)).cast::<sqlite3_vtab>();
+    SQLITE_OKAY
+}
+
+/// Lookups by rowid or name are reported as unique scans. SQLite then updates a row in a
+/// single pass, which keeps the pointer returned by `rembed_client_options()` in `SET
+/// options = ...` intact. Multi-pass updates store new values in a temporary table first,
+/// which drops the pointer.
+unsafe extern "C" fn x_best_index(
+    _vtab: *mut sqlite3_vtab,
+    info: *mut sqlite3_index_info,
+) -> c_int {
+    let info = &mut *info;
+    let constraints = slice::from_raw_parts(info.aConstraint, info.nConstraint as usize);
+    let usages = slice::from_raw_parts_mut(info.aConstraintUsage, info.nConstraint as usize);
+    let lookup =
+        [(-1, ROWID_LOOKUP), (0, NAME_LOOKUP)]
+            .into_iter()
+            .find_map(|(column, idx_num)| {
+                constraints
+                    .iter()
+                    .position(|constraint| {
+                        constraint.usable != 0
+                            && constraint.op == SQLITE_INDEX_CONSTRAINT_EQ
+                            && constraint.iColumn == column
+                    })
+                    .map(|index| (index, idx_num))
+            });
+    match lookup {
+        Some((index, idx_num)) => {
+            // not omitted, so SQLite still checks values that x_filter can't compare
+            usages[index].argvIndex = 1;
+            info.idxNum = idx_num;
+            info.idxFlags = SQLITE_INDEX_SCAN_UNIQUE;
+            info.estimatedCost = 1.0;
+            info.estimatedRows = 1;
+        }
+        None => {
+            info.idxNum = FULL_SCAN;
+            info.estimatedCost = 10000.0;
+            info.estimatedRows = 10000;
+        }
+    }
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_disconnect(vtab: *mut sqlite3_vtab) -> c_int {
+    drop(Box::from_raw(vtab.cast::<ClientsTable>()));
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_destroy(vtab: *mut sqlite3_vtab) -> c_int {
+    if let Err(err) = (*vtab.cast::<ClientsTable>()).destroy() {
+        return set_vtab_error(vtab, err);
+    }
+    x_disconnect(vtab)
+}
+
+unsafe extern "C" fn x_open(
+    _vtab: *mut sqlite3_vtab,
+    pp_cursor: *mut *mut sqlite3_vtab_cursor,
+) -> c_int {
+    let cursor = Box::new(ClientsCursor {
+        base: mem::zeroed(),
+        rows: vec![],
+        index: 0,
+    });
+    *pp_cursor = Box::into_raw(cursor).cast::<sqlite3_vtab_cursor>();
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_close(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    drop(Box::from_raw(cursor.cast::<ClientsCursor>()));
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_filter(
+    cursor: *mut sqlite3_vtab_cursor,
+    idx_num: c_int,
+    _idx_str: *const c_char,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) -> c_int {
+    let vtab = (*cursor).pVtab;
+    let table = &*vtab.cast::<ClientsTable>();
+    let clients_cursor = &mut *cursor.cast::<ClientsCursor>();
+    let args = slice::from_raw_parts(argv, argc as usize);
+    let result = table.rows().and_then(|mut rows| {
+        match (idx_num, args.first()) {
+            (ROWID_LOOKUP, Some(value)) if api::value_type(value) == ValueType::Integer => {
+                let rowid = api::value_int64(value);
+                rows.retain(|(id, _)| *id == rowid);
+            }
+            (NAME_LOOKUP, Some(value)) if api::value_type(value) == ValueType::Text => {
+                let name = api::value_text(value)?;
+                rows.retain(|(_, n)| n == name);
+            }
+            _ => {}
+        }
+        Ok(rows)
+    });
+    match result {
+        Ok(rows) => {
+            clients_cursor.rows = rows;
+            clients_cursor.index = 0;
+            SQLITE_OKAY
+        }
+        Err(err) => set_vtab_error(vtab, err),
+    }
+}
+
+unsafe extern "C" fn x_next(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    (*cursor.cast::<ClientsCursor>()).index += 1;
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_eof(cursor: *mut sqlite3_vtab_cursor) -> c_int {
+    let cursor = &*cursor.cast::<ClientsCursor>();
+    c_int::from(cursor.index >= cursor.rows.len())
+}
+
+unsafe extern "C" fn x_column(
+    cursor: *mut sqlite3_vtab_cursor,
+    context: *mut sqlite3_context,
+    i: c_int,
+) -> c_int {
+    let table = &*(*cursor).pVtab.cast::<ClientsTable>();
+    let (rowid, name) = (*cursor.cast::<ClientsCursor>()).current();
+    // Stored clients that can't be built only show their name
+    let registered = table.client(*rowid, name).ok();
+    let result = match (column(i), registered.as_ref()) {
+        (Some(Columns::Name), _) => api::result_text(context, name),
+        (_, None) => Ok(()),
+        (Some(Columns::Options), Some(registered)) => {
+            api::result_json(context, registered.describe())
+        }
+        (Some(Columns::Format), Some(registered)) => {
+            api::result_text(context, registered.client.format())
+        }
+        (Some(Columns::Model), Some(registered)) => match registered.client.model() {
+            Some(model) => api::result_text(context, model),
+            None => Ok(()),
+        },
+        (Some(Columns::Url), Some(registered)) => match registered.client.url() {
+            Some(url) => api::result_text(context, url),
+            None => Ok(()),
+        },
+        (None, _) => Ok(()),
+    };
+    match result {
+        Ok(()) => SQLITE_OKAY,
+        Err(err) => set_vtab_error((*cursor).pVtab, err),
+    }
+}
+
+unsafe extern "C" fn x_rowid(cursor: *mut sqlite3_vtab_cursor, p_rowid: *mut i64) -> c_int {
+    let (rowid, _) = (*cursor.cast::<ClientsCursor>()).current();
+    *p_rowid = *rowid;
+    SQLITE_OKAY
+}
+
+/// <https://www.sqlite.org/vtab.html#the_xupdate_method>
+unsafe extern "C" fn x_update(
+    vtab: *mut sqlite3_vtab,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+    p_rowid: *mut i64,
+) -> c_int {
+    let table = &*vtab.cast::<ClientsTable>();
+    let args = slice::from_raw_parts(argv, argc as usize);
+    let result = if args.len() == 1 {
+        table.delete(api::value_int64(&args[0]))
+    } else if api::value_type(&args[0]) == ValueType::Null {
+        table.insert(&args[2..]).map(|rowid| *p_rowid = rowid)
+    } else {
+        let rowid = api::value_int64(&args[0]);
+        if api::value_int64(&args[1]) != rowid {
+            Err(Error::new_message(
+                "Changing the rowid of a rembed client is not supported",
+            ))
+        } else {
+            table.update(rowid, &args[2..])
+        }
+    };
+    match result {
+        Ok(()) => SQLITE_OKAY,
+        Err(err) => set_vtab_error(vtab, err),
     }
+}
 
-    fn rowid(&self) -> Result<i64> {
-        Ok(self.rowid)
+unsafe extern "C" fn x_begin(vtab: *mut sqlite3_vtab) -> c_int {
+    let table = &*vtab.cast::<ClientsTable>();
+    table.release(-1);
+    table.savepoint(-1);
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_commit(vtab: *mut sqlite3_vtab) -> c_int {
+    (*vtab.cast::<ClientsTable>()).release(-1);
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_rollback(vtab: *mut sqlite3_vtab) -> c_int {
+    let table = &*vtab.cast::<ClientsTable>();
+    table.rollback_to(-1);
+    table.release(-1);
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_savepoint(vtab: *mut sqlite3_vtab, level: c_int) -> c_int {
+    (*vtab.cast::<ClientsTable>()).savepoint(level);
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_release(vtab: *mut sqlite3_vtab, level: c_int) -> c_int {
+    (*vtab.cast::<ClientsTable>()).release(level);
+    SQLITE_OKAY
+}
+
+unsafe extern "C" fn x_rollback_to(vtab: *mut sqlite3_vtab, level: c_int) -> c_int {
+    (*vtab.cast::<ClientsTable>()).rollback_to(level);
+    SQLITE_OKAY
+}
+
+/// Marks `<table>_clients` as a shadow table, so it can't be written to directly when
+/// SQLITE_DBCONFIG_DEFENSIVE is on.
+unsafe extern "C" fn x_shadow_name(name: *const c_char) -> c_int {
+    c_int::from(CStr::from_ptr(name).to_bytes() == SHADOW_SUFFIX.as_bytes())
+}
+
+static CLIENTS_MODULE: sqlite3_module = sqlite3_module {
+    iVersion: 3,
+    xCreate: Some(x_connect),
+    xConnect: Some(x_connect),
+    xBestIndex: Some(x_best_index),
+    xDisconnect: Some(x_disconnect),
+    xDestroy: Some(x_destroy),
+    xOpen: Some(x_open),
+    xClose: Some(x_close),
+    xFilter: Some(x_filter),
+    xNext: Some(x_next),
+    xEof: Some(x_eof),
+    xColumn: Some(x_column),
+    xRowid: Some(x_rowid),
+    xUpdate: Some(x_update),
+    xBegin: Some(x_begin),
+    xSync: None,
+    xCommit: Some(x_commit),
+    xRollback: Some(x_rollback),
+    xFindFunction: None,
+    xRename: None,
+    xSavepoint: Some(x_savepoint),
+    xRelease: Some(x_release),
+    xRollbackTo: Some(x_rollback_to),
+    xShadowName: Some(x_shadow_name),
+};
+
+unsafe extern "C" fn destroy_aux(p: *mut c_void) {
+    if !p.is_null() {
+        drop(Box::from_raw(p.cast::<Rc<RefCell<ClientRegistry>>>()));
+    }
+}
+
+/// Registers the `rembed_clients` module, which supports INSERT, UPDATE and DELETE on the
+/// clients in `clients`.
+pub fn define_clients_table(db: *mut sqlite3, clients: Rc<RefCell<ClientRegistry>>) -> Result<()> {
+    let name = CString::new(MODULE_NAME)?;
+    let aux = Box::into_raw(Box::new(clients)).cast::<c_void>();
+    let rc = unsafe {
+        sqlite3ext_create_module_v2(db, name.as_ptr(), &CLIENTS_MODULE, aux, Some(destroy_aux))
+    };
+    if rc != SQLITE_OKAY {
+        return Err(Error::new_message(format!(
+            "Error registering rembed_clients module (code {rc})"
+        )));
+    }
+    Ok(())
+}
+
+/// Connects to every persistent `rembed_clients` table in the main schema, which registers
+/// their clients. This is best-effort: when the schema can't be read (e.g. because the
+/// database is locked or isn't a database), each table's clients are registered once the
+/// table itself is first queried.
+pub fn restore_persistent_tables(db: *mut sqlite3) {
+    let mut stmt = match Statement::prepare(
+        db,
+        "select name from main.sqlite_master
+        where type = 'table' and sql like 'create virtual table%using%rembed_clients%'",
+    ) {
+        Ok(stmt) => stmt,
+        Err(_) => return,
+    };
+    let mut tables = vec![];
+    while let Ok(true) = stmt.step() {
+        if let Ok(table) = api::value_text(&stmt.column_value(0)) {
+            tables.push(table.to_owned());
+        }
+    }
+    drop(stmt);
+    for table in tables {
+        let _ = Statement::prepare(
+            db,
+            &format!("select 1 from main.{} limit 0", quote_identifier(&table)),
+        );
     }
 }
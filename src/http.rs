@@ -0,0 +1,315 @@
+use sqlite_loadable::{Error, Result};
+
+use crate::rate_limit::RateLimiter;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How failed requests are retried. Requests that fail with a 408, 409, 429 or 5xx
+/// status code, or that fail to connect or lose their connection, are retried up to
+/// `max_retries` times.
+#[derive(Clone, Debug)]
+pub struct RetryOptions {
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every following attempt.
+    pub base_delay: Duration,
+    /// Upper bound on any single delay, including ones requested by the server.
+    pub max_delay: Duration,
+    /// Whether to randomize each backoff delay between half and all of its value.
+    pub jitter: bool,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+            max_delay: DEFAULT_RETRY_MAX_DELAY,
+            jitter: true,
+        }
+    }
+}
+
+pub(crate) fn parse_option<T: std::str::FromStr>(
+    options: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<T>> {
+    options
+        .get(key)
+        .map(|value| {
+            value.parse().map_err(|_| {
+                Error::new_message(format!("Invalid value '{value}' for '{key}' option"))
+            })
+        })
+        .transpose()
+}
+
+pub(crate) fn parse_bool_option(
+    options: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<bool>> {
+    options
+        .get(key)
+        .map(|value| match value.as_str() {
+            "1" | "true" | "on" | "yes" => Ok(true),
+            "0" | "false" | "off" | "no" => Ok(false),
+            _ => Err(Error::new_message(format!(
+                "Invalid value '{value}' for '{key}' option, expected a boolean"
+            ))),
+        })
+        .transpose()
+}
+
+impl RetryOptions {
+    /// Reads the `max_retries`, `retry_base_delay_ms`, `retry_max_delay_ms` and
+    /// `retry_jitter` keys from `rembed_client_options()`.
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            max_retries: parse_option(options, "max_retries")?.unwrap_or(default.max_retries),
+            base_delay: parse_option(options, "retry_base_delay_ms")?
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+            max_delay: parse_option(options, "retry_max_delay_ms")?
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_delay),
+            jitter: parse_bool_option(options, "retry_jitter")?.unwrap_or(default.jitter),
+        })
+    }
+
+    /// The delay before retry number `attempt` (starting at 0). A delay requested by
+    /// the server takes precedence over exponential backoff.
+    fn delay(&self, attempt: u32, requested: Option<Duration>) -> Duration {
+        if let Some(requested) = requested {
+            return requested.min(self.max_delay);
+        }
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        if self.jitter {
+            backoff / 2 + backoff.mul_f64(random_fraction() / 2.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// A random number in `[0, 1)`, from the randomly-seeded keys of std's `RandomState`.
+fn random_fraction() -> f64 {
+    let n = RandomState::new().build_hasher().finish();
+    (n >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 409 | 429 | 500..=599)
+}
+
+/// Whether a request that failed without a response could succeed when retried. Invalid
+/// URLs and DNS failures fail the same way every time.
+fn is_retryable_transport(transport: &ureq::Transport) -> bool {
+    matches!(
+        transport.kind(),
+        ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+    )
+}
+
+/// A delay of `seconds`, at most `max`. Server-provided values can be infinite or too
+/// large for a [`Duration`].
+fn clamped_delay(seconds: f64, max: Duration) -> Option<Duration> {
+    Duration::try_from_secs_f64(seconds.max(0.0).min(max.as_secs_f64())).ok()
+}
+
+/// Parses durations like `1s`, `6m0s`, `20ms` or `1h2m3.5s`, as used by the
+/// `x-ratelimit-reset-*` headers, up to `max`.
+fn parse_reset_duration(value: &str, max: Duration) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        total += match unit {
+            "h" => number * 3600.0,
+            "m" => number * 60.0,
+            "s" | "" => number,
+            "ms" => number / 1000.0,
+            _ => return None,
+        };
+        rest = tail;
+    }
+    clamped_delay(total, max)
+}
+
+/// Parses an IMF-fixdate like `Wed, 21 Oct 2015 07:28:00 GMT` into seconds since the epoch.
+/// Out-of-range fields, like years past 9999, aren't valid dates.
+fn parse_http_date(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+    let day: i64 = parts[1].parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|m| *m == parts[2])? as i64
+        + 1;
+    let year: i64 = parts[3]
+        .parse()
+        .ok()
+        .filter(|year| (1970..=9999).contains(year))?;
+    let time: Vec<i64> = parts[4]
+        .split(':')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    // up to 60 seconds, for leap seconds
+    if time.len() != 3
+        || !(0..24).contains(&time[0])
+        || !(0..60).contains(&time[1])
+        || !(0..=60).contains(&time[2])
+    {
+        return None;
+    }
+    // days_from_civil, from http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2]).ok()
+}
+
+/// How long the server asked us to wait before retrying, from the `retry-after-ms`,
+/// `Retry-After` or `x-ratelimit-reset-requests`/`x-ratelimit-reset-tokens` headers, up
+/// to `max`.
+fn requested_delay(response: &ureq::Response, max: Duration) -> Option<Duration> {
+    if let Some(ms) = response
+        .header("retry-after-ms")
+        .and_then(|v| v.trim().parse::<f64>().ok())
+    {
+        return clamped_delay(ms / 1000.0, max);
+    }
+    if let Some(retry_after) = response.header("retry-after") {
+        if let Ok(seconds) = retry_after.trim().parse::<f64>() {
+            return clamped_delay(seconds, max);
+        }
+        if let Some(date) = parse_http_date(retry_after) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            return Some(Duration::from_secs(date.saturating_sub(now)));
+        }
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .iter()
+        .filter_map(|header| {
+            response
+                .header(header)
+                .and_then(|value| parse_reset_duration(value, max))
+        })
+        .max()
+}
+
+/// A response status that a client handles itself, when the function accepts the response
+/// body. See [`Transport::post_json`].
+pub type UnlessStatus = (u16, fn(&str) -> bool);
+
+/// Sends requests on behalf of a client, handling JSON (de)serialization, rate limiting
+/// and retries.
+#[derive(Clone, Debug, Default)]
+pub struct Transport {
+    pub retry: RetryOptions,
+    pub limiter: Option<RateLimiter>,
+}
+
+impl Transport {
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Self> {
+        Ok(Self {
+            retry: RetryOptions::from_options(options)?,
+            limiter: RateLimiter::from_options(options)?,
+        })
+    }
+
+    /// Sends `body` as JSON with the given request, and parses the response body as JSON.
+    /// `estimated_tokens` is drawn from the client's rate limiter on every attempt. When the
+    /// server responds with the status of `unless` and the function accepts the response
+    /// body, `None` is returned without retrying, for clients that fall back to another
+    /// endpoint. Other responses with that status are errors that include their body.
+    pub fn post_json(
+        &self,
+        request: ureq::Request,
+        body: &serde_json::Value,
+        estimated_tokens: u64,
+        unless: Option<UnlessStatus>,
+    ) -> Result<Option<serde_json::Value>> {
+        let body = serde_json::to_vec(body).map_err(|error| {
+            Error::new_message(format!("Error serializing body to JSON: {error}"))
+        })?;
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(estimated_tokens);
+            }
+            let error = match request.clone().send_bytes(&body) {
+                Ok(response) => {
+                    return response.into_json().map(Some).map_err(|error| {
+                        Error::new_message(format!("Error parsing HTTP response as JSON: {error}"))
+                    })
+                }
+                Err(ureq::Error::Status(code, response))
+                    if unless.is_some_and(|(status, _)| status == code) =>
+                {
+                    let url = response.get_url().to_owned();
+                    let body = response.into_string().unwrap_or_default();
+                    return match unless {
+                        Some((_, accepts)) if accepts(&body) => Ok(None),
+                        _ => Err(Error::new_message(format!(
+                            "Error sending HTTP request: {url}: status code {code}: {}",
+                            body.trim()
+                        ))),
+                    };
+                }
+                Err(error) => error,
+            };
+            let requested = match &error {
+                ureq::Error::Status(status, response) if is_retryable_status(*status) => {
+                    requested_delay(response, self.retry.max_delay)
+                }
+                ureq::Error::Transport(transport) if is_retryable_transport(transport) => None,
+                _ => {
+                    return Err(Error::new_message(format!(
+                        "Error sending HTTP request: {error}"
+                    )))
+                }
+            };
+            if attempt >= self.retry.max_retries {
+                return Err(Error::new_message(format!(
+                    "Error sending HTTP request: {error} (after {} attempts)",
+                    attempt + 1
+                )));
+            }
+            thread::sleep(self.retry.delay(attempt, requested));
+            attempt += 1;
+        }
+    }
+}
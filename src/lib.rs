@@ -1,19 +1,32 @@
+mod batch_vtab;
+mod cache;
 mod clients;
 mod clients_vtab;
+mod http;
+mod oauth;
+mod rate_limit;
+mod sigv4;
+mod sql;
+mod vector;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use clients::{Client, CohereClient, LlamafileClient, NomicClient, OllamaClient, OpenAiClient};
-use clients_vtab::ClientsTable;
+use batch_vtab::BatchTable;
+use clients::{ClientRegistry, RegisteredClient};
+use sql::Statement;
 use sqlite_loadable::{
-    api, define_scalar_function, define_scalar_function_with_aux, define_virtual_table_writeablex,
-    prelude::*, Error, Result,
+    api::{self, ValueType},
+    define_scalar_function, define_scalar_function_with_aux, define_table_function,
+    prelude::*,
+    Error, Result,
 };
 use zerocopy::AsBytes;
 
-const FLOAT32_VECTOR_SUBTYPE: u8 = 223;
+pub use clients::EmbeddingClient;
+pub use vector::VectorType;
+
 const CLIENT_OPTIONS_POINTER_NAME: &[u8] = b"sqlite-rembed-client-options\0";
 
 pub fn rembed_version(context: *mut sqlite3_context, _values: &[*mut sqlite3_value]) -> Result<()> {
@@ -39,107 +52,228 @@ pub fn rembed_client_options(
     context: *mut sqlite3_context,
     values: &[*mut sqlite3_value],
 ) -> Result<()> {
-    if (values.len() % 2) != 0 {
+    if !values.len().is_multiple_of(2) {
         return Err(Error::new_message(
             "Must have an even number of arguments to rembed_client_options, as key/value pairs.",
         ));
     }
     let mut options: HashMap<String, String> = HashMap::new();
-    let mut format: Option<String> = None;
     for pair in values.chunks(2) {
         let key = api::value_text(&pair[0])?;
         let value = api::value_text(&pair[1])?;
-        if key == "format" {
-            format = Some(value.to_owned());
-        } else {
-            options.insert(key.to_owned(), value.to_owned());
-        }
+        options.insert(key.to_owned(), value.to_owned());
     }
-
-    let format = match format {
-        Some(format) => format,
-        None => {
-            return Err(Error::new_message("'format' key is required."));
-        }
-    };
-    let client: Client = match format.as_str() {
-        "openai" => Client::OpenAI(OpenAiClient::new(
-            options
-                .get("model")
-                .ok_or_else(|| Error::new_message("'model' option is required"))?,
-            options.get("url").cloned(),
-            options.get("key").cloned(),
-        )?),
-        "nomic" => Client::Nomic(NomicClient::new(
-            options
-                .get("model")
-                .ok_or_else(|| Error::new_message("'model' option is required"))?,
-            options.get("url").cloned(),
-            options.get("key").cloned(),
-        )?),
-        "cohere" => Client::Cohere(CohereClient::new(
-            options
-                .get("model")
-                .ok_or_else(|| Error::new_message("'model' option is required"))?,
-            options.get("url").cloned(),
-            options.get("key").cloned(),
-        )?),
-        "ollama" => Client::Ollama(OllamaClient::new(
-            options
-                .get("model")
-                .ok_or_else(|| Error::new_message("'model' option is required"))?,
-            options.get("url").cloned(),
-        )),
-        "llamafile" => Client::Llamafile(LlamafileClient::new(options.get("url").cloned())),
-        format => return Err(Error::new_message(format!("Unknown format '{format}'"))),
-    };
+    let client = RegisteredClient::from_options(options)?;
 
     api::result_pointer(context, CLIENT_OPTIONS_POINTER_NAME, client);
 
     Ok(())
 }
-pub fn rembed(
+
+/// The embedding for `rembed()`'s arguments, read from the cache when the client has one,
+/// and the type to encode it as.
+fn embed(
     context: *mut sqlite3_context,
     values: &[*mut sqlite3_value],
-    clients: &Rc<RefCell<HashMap<String, Client>>>,
-) -> Result<()> {
+    clients: &Rc<RefCell<ClientRegistry>>,
+) -> Result<(Vec<f32>, VectorType)> {
     let client_name = api::value_text(&values[0])?;
     let input = api::value_text(&values[1])?;
+    let input_type = values.get(2).and_then(|v| api::value_text(v).ok());
     let x = clients.borrow();
-    let client = x.get(client_name).ok_or_else(|| {
-        Error::new_message(format!(
-            "Client with name {client_name} was not registered with rembed_clients."
-        ))
-    })?;
+    let registered = x.lookup(client_name)?;
+    let input_type = input_type.or(registered.input_type.as_deref());
 
-    let embedding = match client {
-        Client::OpenAI(client) => client.infer_single(input)?,
-        Client::Jina(client) => client.infer_single(input)?,
-        Client::Mixedbread(client) => client.infer_single(input)?,
-        Client::Ollama(client) => client.infer_single(input)?,
-        Client::Llamafile(client) => client.infer_single(input)?,
-        Client::Nomic(client) => {
-            let input_type = values.get(2).and_then(|v| api::value_text(v).ok());
-            client.infer_single(input, input_type)?
-        }
-        Client::Cohere(client) => {
-            let input_type = values.get(2).and_then(|v| api::value_text(v).ok());
-            client.infer_single(input, input_type)?
+    let embedding = match &registered.cache {
+        Some(stats) => {
+            let db = api::context_db_handle(context);
+            let fingerprint = registered.fingerprint();
+            let key = cache::key(&fingerprint, input_type, input);
+            match x.cache_table().get(db, &key)? {
+                Some(embedding) => {
+                    stats.hits.set(stats.hits.get() + 1);
+                    embedding
+                }
+                None => {
+                    stats.misses.set(stats.misses.get() + 1);
+                    let embedding = registered.infer(input, input_type)?;
+                    x.cache_table().put(
+                        db,
+                        &key,
+                        &cache::namespace(&fingerprint),
+                        embedding.as_bytes(),
+                    )?;
+                    embedding
+                }
+            }
         }
+        None => registered.infer(input, input_type)?,
     };
 
-    api::result_blob(context, embedding.as_bytes());
-    api::result_subtype(context, FLOAT32_VECTOR_SUBTYPE);
+    Ok((embedding, registered.vector_type))
+}
+
+pub fn rembed(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    clients: &Rc<RefCell<ClientRegistry>>,
+) -> Result<()> {
+    let (embedding, vector_type) = embed(context, values, clients)?;
+    api::result_blob(context, &vector_type.to_blob(&embedding));
+    api::result_subtype(context, vector_type.subtype());
     Ok(())
 }
 
-#[sqlite_entrypoint]
-pub fn sqlite3_rembed_init(db: *mut sqlite3) -> Result<()> {
-    let flags = FunctionFlags::UTF8
-        | FunctionFlags::DETERMINISTIC
-        | unsafe { FunctionFlags::from_bits_unchecked(0x001000000) };
+/// `rembed_json(client, input [, input_type])`: like `rembed()`, but returns the embedding
+/// as a JSON array, in the format of `rembed_blob_to_json()`.
+pub fn rembed_json(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    clients: &Rc<RefCell<ClientRegistry>>,
+) -> Result<()> {
+    let (embedding, vector_type) = embed(context, values, clients)?;
+    api::result_json(
+        context,
+        vector_type.blob_to_json(&vector_type.to_blob(&embedding))?,
+    )
+}
+
+/// The vector type named by an optional `vector_type` argument, or else the one tagged by
+/// `subtype`. BLOBs read back from a table lose their subtype, so are read as `float32`.
+fn vector_type_argument(argument: Option<&*mut sqlite3_value>, subtype: u8) -> Result<VectorType> {
+    match argument {
+        Some(value) => {
+            let name = api::value_text(value)?;
+            VectorType::from_name(name).ok_or_else(|| {
+                Error::new_message(format!(
+                    "Invalid vector type '{name}', expected one of float32, int8, bit"
+                ))
+            })
+        }
+        None => Ok(VectorType::from_subtype(subtype).unwrap_or_default()),
+    }
+}
 
-    let c = Rc::new(RefCell::new(HashMap::new()));
+/// `rembed_blob_to_json(blob [, vector_type])`: decodes a `sqlite-vec` BLOB into a JSON
+/// array. The vector type comes from the BLOB's subtype unless it's given. NULL stays NULL.
+pub fn rembed_blob_to_json(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+) -> Result<()> {
+    if api::value_type(&values[0]) == ValueType::Null {
+        api::result_null(context);
+        return Ok(());
+    }
+    let subtype = api::value_subtype(&values[0]) as u8;
+    let vector_type = vector_type_argument(values.get(1), subtype)?;
+    api::result_json(
+        context,
+        vector_type.blob_to_json(sql::value_blob(&values[0]))?,
+    )
+}
+
+/// `rembed_json_to_blob(json [, vector_type])`: encodes a JSON array as a `sqlite-vec`
+/// BLOB, `float32` unless another vector type is given, tagged with its subtype. NULL stays
+/// NULL.
+pub fn rembed_json_to_blob(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+) -> Result<()> {
+    if api::value_type(&values[0]) == ValueType::Null {
+        api::result_null(context);
+        return Ok(());
+    }
+    let vector_type = vector_type_argument(values.get(1), 0)?;
+    let json: serde_json::Value = serde_json::from_str(api::value_text(&values[0])?)
+        .map_err(|error| Error::new_message(format!("Invalid JSON array: {error}")))?;
+    api::result_blob(context, &vector_type.json_to_blob(&json)?);
+    api::result_subtype(context, vector_type.subtype());
+    Ok(())
+}
+
+/// `rembed_cache_clear(client)`: deletes every embedding cached for the client's
+/// configuration, returning the number of deleted rows.
+pub fn rembed_cache_clear(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    clients: &Rc<RefCell<ClientRegistry>>,
+) -> Result<()> {
+    let client_name = api::value_text(&values[0])?;
+    let x = clients.borrow();
+    let registered = x.lookup(client_name)?;
+    let namespace = cache::namespace(&registered.fingerprint());
+    let deleted = x
+        .cache_table()
+        .clear(api::context_db_handle(context), &namespace)?;
+    api::result_int64(context, deleted);
+    Ok(())
+}
+
+/// `rembed_cache_stats(client)`: cache hits and misses for the client in this connection,
+/// as a JSON object. Returns NULL when the client doesn't have caching enabled.
+pub fn rembed_cache_stats(
+    context: *mut sqlite3_context,
+    values: &[*mut sqlite3_value],
+    clients: &Rc<RefCell<ClientRegistry>>,
+) -> Result<()> {
+    let client_name = api::value_text(&values[0])?;
+    let x = clients.borrow();
+    match &x.lookup(client_name)?.cache {
+        Some(stats) => api::result_json(
+            context,
+            serde_json::json!({
+                "hits": stats.hits.get(),
+                "misses": stats.misses.get(),
+            }),
+        )?,
+        None => api::result_null(context),
+    }
+    Ok(())
+}
+
+/// Registers a custom client under `name` on a connection that `sqlite3_rembed_init()` has
+/// run on, like an `INSERT INTO temp.rembed_clients`. The client can then be used from
+/// `rembed()` and `rembed_batch()`, and replaces any client with the same name.
+pub fn register_client(
+    db: *mut sqlite3,
+    name: &str,
+    client: Box<dyn EmbeddingClient>,
+) -> Result<()> {
+    let client = RegisteredClient::new(Rc::from(client));
+    let mut stmt = Statement::prepare(
+        db,
+        "insert into temp.rembed_clients(name, options) values (?, ?)",
+    )
+    .map_err(|_| {
+        Error::new_message("rembed_clients is not available, sqlite3_rembed_init() must be called on this connection first")
+    })?;
+    stmt.bind_text(1, name)?;
+    stmt.bind_pointer(2, &client, CLIENT_OPTIONS_POINTER_NAME)?;
+    while stmt.step()? {}
+    Ok(())
+}
+
+/// # Safety
+///
+/// Should only be called by SQLite, when the extension is loaded or registered with
+/// `sqlite3_auto_extension()`. Written out rather than with `#[sqlite_entrypoint]`, to keep
+/// the API routines that `sqlite_loadable` doesn't wrap.
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_rembed_init(
+    db: *mut sqlite3,
+    pz_err_msg: *mut *mut c_char,
+    p_api: *mut sqlite3_api_routines,
+) -> c_uint {
+    sql::init_api(p_api);
+    register_entrypoint(db, pz_err_msg, p_api, rembed_init)
+}
+
+fn rembed_init(db: *mut sqlite3) -> Result<()> {
+    // SQLITE_RESULT_SUBTYPE, for functions that call sqlite3_result_subtype()
+    let result_subtype = unsafe { FunctionFlags::from_bits_unchecked(0x001000000) };
+    let flags = FunctionFlags::UTF8 | FunctionFlags::DETERMINISTIC | result_subtype;
+
+    let c = Rc::new(RefCell::new(ClientRegistry::default()));
 
     define_scalar_function(
         db,
@@ -157,6 +291,20 @@ pub fn sqlite3_rembed_init(db: *mut sqlite3) -> Result<()> {
     )?;
     define_scalar_function_with_aux(db, "rembed", 2, rembed, flags, Rc::clone(&c))?;
     define_scalar_function_with_aux(db, "rembed", 3, rembed, flags, Rc::clone(&c))?;
+    define_scalar_function_with_aux(db, "rembed_json", 2, rembed_json, flags, Rc::clone(&c))?;
+    define_scalar_function_with_aux(db, "rembed_json", 3, rembed_json, flags, Rc::clone(&c))?;
+    // SQLITE_SUBTYPE, for functions that call sqlite3_value_subtype()
+    let value_subtype = unsafe { FunctionFlags::from_bits_unchecked(0x000100000) };
+    for argc in [1, 2] {
+        define_scalar_function(
+            db,
+            "rembed_blob_to_json",
+            argc,
+            rembed_blob_to_json,
+            flags | value_subtype,
+        )?;
+        define_scalar_function(db, "rembed_json_to_blob", argc, rembed_json_to_blob, flags)?;
+    }
     define_scalar_function(
         db,
         "rembed_client_options",
@@ -164,6 +312,25 @@ pub fn sqlite3_rembed_init(db: *mut sqlite3) -> Result<()> {
         rembed_client_options,
         flags,
     )?;
-    define_virtual_table_writeablex::<ClientsTable>(db, "rembed_clients", Some(Rc::clone(&c)))?;
+    define_scalar_function_with_aux(
+        db,
+        "rembed_cache_clear",
+        1,
+        rembed_cache_clear,
+        FunctionFlags::UTF8,
+        Rc::clone(&c),
+    )?;
+    define_scalar_function_with_aux(
+        db,
+        "rembed_cache_stats",
+        1,
+        rembed_cache_stats,
+        FunctionFlags::UTF8 | result_subtype,
+        Rc::clone(&c),
+    )?;
+    clients_vtab::define_clients_table(db, Rc::clone(&c))?;
+    define_table_function::<BatchTable>(db, "rembed_batch", Some(Rc::clone(&c)))?;
+    // Must not fail, as the functions and module above are already registered
+    clients_vtab::restore_persistent_tables(db);
     Ok(())
 }
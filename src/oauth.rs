@@ -0,0 +1,277 @@
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ring::{rand::SystemRandom, signature};
+use sqlite_loadable::{Error, Result};
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+/// How long minted tokens are requested for, the maximum Google allows.
+const TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+/// Tokens are refreshed this long before they expire, so requests never race the expiry.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// A Google Cloud service account, from a JSON key file.
+pub struct ServiceAccount {
+    pub client_email: String,
+    pub project_id: Option<String>,
+    pub token_uri: String,
+    key_pair: signature::RsaKeyPair,
+}
+
+impl ServiceAccount {
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|error| {
+            Error::new_message(format!("Error reading service account key {path}: {error}"))
+        })?;
+        let key: serde_json::Value = serde_json::from_str(&contents).map_err(|error| {
+            Error::new_message(format!("Error parsing service account key {path}: {error}"))
+        })?;
+        let field = |name: &str| {
+            key.get(name).and_then(|v| v.as_str()).ok_or_else(|| {
+                Error::new_message(format!(
+                    "expected '{name}' key in service account key {path}"
+                ))
+            })
+        };
+
+        let pem = field("private_key")?;
+        let der = STANDARD
+            .decode(
+                pem.lines()
+                    .filter(|line| !line.starts_with("-----"))
+                    .collect::<String>(),
+            )
+            .map_err(|error| {
+                Error::new_message(format!("Invalid private key in {path}: {error}"))
+            })?;
+        let key_pair = signature::RsaKeyPair::from_pkcs8(&der).map_err(|error| {
+            Error::new_message(format!("Invalid private key in {path}: {error}"))
+        })?;
+
+        Ok(Self {
+            client_email: field("client_email")?.to_owned(),
+            project_id: field("project_id").ok().map(|id| id.to_owned()),
+            token_uri: field("token_uri").unwrap_or(DEFAULT_TOKEN_URI).to_owned(),
+            key_pair,
+        })
+    }
+
+    /// A JWT signed with RS256, to exchange for an access token at `token_uri`.
+    fn assertion(&self, scope: &str, token_uri: &str, now: SystemTime) -> Result<String> {
+        let iat = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let header = serde_json::json!({ "alg": "RS256", "typ": "JWT" });
+        let claims = serde_json::json!({
+            "iss": self.client_email,
+            "scope": scope,
+            "aud": token_uri,
+            "iat": iat,
+            "exp": iat + TOKEN_LIFETIME.as_secs(),
+        });
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let mut signature = vec![0; self.key_pair.public().modulus_len()];
+        self.key_pair
+            .sign(
+                &signature::RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| Error::new_message("Error signing service account assertion"))?;
+        Ok(format!("{message}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+/// Mints access tokens for a service account, and reuses each one until shortly before it
+/// expires.
+pub struct TokenSource {
+    account: ServiceAccount,
+    token_uri: String,
+    scope: String,
+    cached: RefCell<Option<(String, Instant)>>,
+}
+
+impl TokenSource {
+    /// Tokens for `scope`, from the account's `token_uri` or the given override.
+    pub fn new(account: ServiceAccount, token_uri: Option<String>, scope: &str) -> Self {
+        Self {
+            token_uri: token_uri.unwrap_or_else(|| account.token_uri.clone()),
+            account,
+            scope: scope.to_owned(),
+            cached: RefCell::new(None),
+        }
+    }
+
+    pub fn token(&self) -> Result<String> {
+        if let Some((token, expires_at)) = &*self.cached.borrow() {
+            if Instant::now() + REFRESH_MARGIN < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let assertion = self
+            .account
+            .assertion(&self.scope, &self.token_uri, SystemTime::now())?;
+        let requested_at = Instant::now();
+        let response: serde_json::Value = ureq::post(&self.token_uri)
+            .send_form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .map_err(|error| {
+                Error::new_message(format!("Error requesting an access token: {error}"))
+            })?
+            .into_json()
+            .map_err(|error| {
+                Error::new_message(format!("Error parsing access token response: {error}"))
+            })?;
+        let token = response
+            .get("access_token")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                Error::new_message("expected 'access_token' key in access token response")
+            })?
+            .to_owned();
+        let expires_in = response
+            .get("expires_in")
+            .and_then(|v| v.as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(TOKEN_LIFETIME);
+
+        *self.cached.borrow_mut() = Some((token.clone(), requested_at + expires_in));
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+    /// A service account with a key generated for these tests only.
+    fn account() -> ServiceAccount {
+        ServiceAccount::from_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/testdata/service-account.json"
+        ))
+        .unwrap()
+    }
+
+    /// Serves one of `responses` to each request at the returned `token_url`, and sends each
+    /// request's form body back over the channel.
+    fn serve_tokens(responses: Vec<serde_json::Value>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(String::from_utf8(body).unwrap()).unwrap();
+
+                let response = response.to_string();
+                write!(
+                    &stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                    response.len()
+                )
+                .unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn token_is_minted_once_and_reused() {
+        let (url, requests) = serve_tokens(vec![
+            serde_json::json!({ "access_token": "tok-1", "expires_in": 3600 }),
+        ]);
+        let account = account();
+        let public_key = account.key_pair.public().as_ref().to_vec();
+        let tokens = TokenSource::new(account, Some(url.clone()), SCOPE);
+
+        assert_eq!(tokens.token().unwrap(), "tok-1");
+        assert_eq!(tokens.token().unwrap(), "tok-1");
+
+        let form = requests.recv().unwrap();
+        assert!(requests.try_recv().is_err());
+        let fields: Vec<&str> = form.split('&').collect();
+        assert!(
+            fields.contains(&"grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer")
+        );
+        let assertion = fields
+            .iter()
+            .find_map(|field| field.strip_prefix("assertion="))
+            .unwrap();
+
+        let (message, signature) = assertion.rsplit_once('.').unwrap();
+        signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, public_key)
+            .verify(
+                message.as_bytes(),
+                &URL_SAFE_NO_PAD.decode(signature).unwrap(),
+            )
+            .unwrap();
+        let claims: serde_json::Value = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(message.split_once('.').unwrap().1)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            claims["iss"],
+            "test@sqlite-rembed-test.iam.gserviceaccount.com"
+        );
+        assert_eq!(claims["scope"], SCOPE);
+        assert_eq!(claims["aud"], url);
+        assert_eq!(
+            claims["exp"].as_u64().unwrap() - claims["iat"].as_u64().unwrap(),
+            TOKEN_LIFETIME.as_secs()
+        );
+    }
+
+    #[test]
+    fn token_is_refreshed_before_it_expires() {
+        let (url, requests) = serve_tokens(vec![
+            serde_json::json!({ "access_token": "tok-1", "expires_in": 60 }),
+            serde_json::json!({ "access_token": "tok-2", "expires_in": 60 }),
+        ]);
+        let tokens = TokenSource::new(account(), Some(url), SCOPE);
+
+        assert_eq!(tokens.token().unwrap(), "tok-1");
+        assert_eq!(tokens.token().unwrap(), "tok-2");
+        assert_eq!(requests.iter().count(), 2);
+    }
+}
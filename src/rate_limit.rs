@@ -0,0 +1,147 @@
+use sqlite_loadable::{Error, Result};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Rough token count for `inputs`, at ~4 characters per token.
+pub fn estimate_tokens(inputs: &[&str]) -> u64 {
+    inputs
+        .iter()
+        .map(|input| (input.chars().count() as u64).div_ceil(4).max(1))
+        .sum()
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: f64) -> Self {
+        Self {
+            capacity: limit,
+            available: limit,
+            per_second: limit / 60.0,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Requests larger than the whole bucket are clamped to its capacity, so they wait for
+    /// a full bucket instead of forever.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.per_second)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    refilled_at: Instant,
+}
+
+/// Client-side token-bucket rate limiter, configured with the `rpm` (requests per minute)
+/// and `tpm` (tokens per minute) options. Clones share the same buckets, so every copy of
+/// a registered client draws from the same budget.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    buckets: Rc<RefCell<Buckets>>,
+}
+
+fn parse_limit(options: &HashMap<String, String>, key: &str) -> Result<Option<f64>> {
+    options
+        .get(key)
+        .map(|value| match value.parse::<f64>() {
+            Ok(limit) if limit > 0.0 => Ok(limit),
+            _ => Err(Error::new_message(format!(
+                "Invalid value '{value}' for '{key}' option, expected a positive number"
+            ))),
+        })
+        .transpose()
+}
+
+impl RateLimiter {
+    /// Returns `None` when neither `rpm` nor `tpm` were given.
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Option<Self>> {
+        let requests = parse_limit(options, "rpm")?.map(Bucket::per_minute);
+        let tokens = parse_limit(options, "tpm")?.map(Bucket::per_minute);
+        if requests.is_none() && tokens.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            buckets: Rc::new(RefCell::new(Buckets {
+                requests,
+                tokens,
+                refilled_at: Instant::now(),
+            })),
+        }))
+    }
+
+    /// The `rpm` limit, if any.
+    pub fn rpm(&self) -> Option<f64> {
+        self.buckets.borrow().requests.as_ref().map(|b| b.capacity)
+    }
+
+    /// The `tpm` limit, if any.
+    pub fn tpm(&self) -> Option<f64> {
+        self.buckets.borrow().tokens.as_ref().map(|b| b.capacity)
+    }
+
+    /// Blocks until one request carrying `tokens` tokens fits in the budget, then takes it.
+    pub fn acquire(&self, tokens: u64) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.borrow_mut();
+                let now = Instant::now();
+                let elapsed = now.duration_since(buckets.refilled_at);
+                buckets.refilled_at = now;
+                if let Some(bucket) = buckets.requests.as_mut() {
+                    bucket.refill(elapsed);
+                }
+                if let Some(bucket) = buckets.tokens.as_mut() {
+                    bucket.refill(elapsed);
+                }
+
+                let wait = [
+                    buckets.requests.as_ref().map(|b| b.wait_for(1.0)),
+                    buckets.tokens.as_ref().map(|b| b.wait_for(tokens as f64)),
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .unwrap_or(Duration::ZERO);
+
+                if wait.is_zero() {
+                    if let Some(bucket) = buckets.requests.as_mut() {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = buckets.tokens.as_mut() {
+                        bucket.take(tokens as f64);
+                    }
+                    return;
+                }
+                wait
+            };
+            thread::sleep(wait);
+        }
+    }
+}
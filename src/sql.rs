@@ -0,0 +1,158 @@
+use sqlite_loadable::{
+    api,
+    ext::{
+        sqlite3_api_routines, sqlite3_stmt, sqlite3ext_bind_int64, sqlite3ext_bind_pointer,
+        sqlite3ext_bind_text, sqlite3ext_column_value, sqlite3ext_finalize, sqlite3ext_prepare_v2,
+        sqlite3ext_step,
+    },
+    prelude::*,
+    Error, Result, SQLITE_DONE, SQLITE_OKAY, SQLITE_ROW,
+};
+use std::{
+    ffi::{c_void, CString},
+    mem,
+    os::raw::c_int,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// The API routines SQLite passed to the extension's entrypoint, for the few that
+/// `sqlite_loadable::ext` doesn't wrap.
+static API: AtomicPtr<sqlite3_api_routines> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Keeps the API routines that SQLite passed to the extension's entrypoint.
+pub fn init_api(api: *mut sqlite3_api_routines) {
+    API.store(api, Ordering::Relaxed);
+}
+
+/// The `SQLITE_TRANSIENT` destructor, which makes SQLite copy bound values.
+fn transient() -> Option<unsafe extern "C" fn(*mut c_void)> {
+    Some(unsafe { mem::transmute::<isize, unsafe extern "C" fn(*mut c_void)>(-1) })
+}
+
+/// Minimal prepared statement wrapper, for the extension's own queries against the
+/// connection it was loaded into.
+pub struct Statement {
+    stmt: *mut sqlite3_stmt,
+}
+
+impl Statement {
+    pub fn prepare(db: *mut sqlite3, sql: &str) -> Result<Self> {
+        let c_sql = CString::new(sql)?;
+        let mut stmt: *mut sqlite3_stmt = std::ptr::null_mut();
+        let rc = unsafe {
+            sqlite3ext_prepare_v2(db, c_sql.as_ptr(), -1, &mut stmt, std::ptr::null_mut())
+        };
+        if rc != SQLITE_OKAY {
+            return Err(Error::new_message(format!(
+                "Error preparing statement (code {rc}): {sql}"
+            )));
+        }
+        Ok(Self { stmt })
+    }
+
+    fn check_bind(rc: c_int, i: i32) -> Result<()> {
+        if rc != SQLITE_OKAY {
+            return Err(Error::new_message(format!(
+                "Error binding parameter {i} (code {rc})"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Binds `text` to parameter `i` (starting at 1). SQLite makes its own copy.
+    pub fn bind_text(&mut self, i: i32, text: &str) -> Result<()> {
+        let rc = unsafe {
+            sqlite3ext_bind_text(
+                self.stmt,
+                i,
+                text.as_ptr().cast::<c_char>(),
+                text.len() as c_int,
+                transient(),
+            )
+        };
+        Self::check_bind(rc, i)
+    }
+
+    /// Binds `blob` to parameter `i` (starting at 1). SQLite makes its own copy.
+    pub fn bind_blob(&mut self, i: i32, blob: &[u8]) -> Result<()> {
+        let bind_blob = unsafe { API.load(Ordering::Relaxed).as_ref() }
+            .and_then(|api| api.bind_blob)
+            .ok_or_else(|| Error::new_message("sqlite3_bind_blob() is not available"))?;
+        let rc = unsafe {
+            bind_blob(
+                self.stmt,
+                i,
+                blob.as_ptr().cast::<c_void>(),
+                blob.len() as c_int,
+                transient(),
+            )
+        };
+        Self::check_bind(rc, i)
+    }
+
+    pub fn bind_int64(&mut self, i: i32, value: i64) -> Result<()> {
+        Self::check_bind(unsafe { sqlite3ext_bind_int64(self.stmt, i, value) }, i)
+    }
+
+    /// Binds `pointer` to parameter `i` with the pointer type `name`, which must be
+    /// nul-terminated. The pointer must stay valid until the statement is dropped.
+    pub fn bind_pointer<T>(
+        &mut self,
+        i: i32,
+        pointer: *const T,
+        name: &'static [u8],
+    ) -> Result<()> {
+        let rc = unsafe {
+            sqlite3ext_bind_pointer(
+                self.stmt,
+                i,
+                pointer as *mut c_void,
+                name.as_ptr().cast::<c_char>(),
+            )
+        };
+        Self::check_bind(rc, i)
+    }
+
+    /// Steps the statement, returning `true` when a row is available.
+    pub fn step(&mut self) -> Result<bool> {
+        match unsafe { sqlite3ext_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            rc => Err(Error::new_message(format!(
+                "Error executing statement (code {rc})"
+            ))),
+        }
+    }
+
+    /// The value of column `i` in the current row, valid until the next `step()`.
+    pub fn column_value(&self, i: i32) -> *mut sqlite3_value {
+        unsafe { sqlite3ext_column_value(self.stmt, i) }
+    }
+}
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        unsafe { sqlite3ext_finalize(self.stmt) };
+    }
+}
+
+/// Runs a statement to completion, ignoring any rows it returns.
+pub fn execute(db: *mut sqlite3, sql: &str) -> Result<()> {
+    let mut stmt = Statement::prepare(db, sql)?;
+    while stmt.step()? {}
+    Ok(())
+}
+
+/// The bytes of a BLOB value, like `api::value_blob()`, but empty for NULL and zero-length
+/// values, whose `sqlite3_value_blob()` pointer is null.
+pub fn value_blob<'a>(value: &*mut sqlite3_value) -> &'a [u8] {
+    if api::value_bytes(value) == 0 {
+        return &[];
+    }
+    api::value_blob(value)
+}
+
+/// Quotes a schema, table or column name for use in SQL, like `"my ""table"""`.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
@@ -0,0 +1,204 @@
+use sqlite_loadable::{Error, Result};
+use zerocopy::AsBytes;
+
+pub const FLOAT32_VECTOR_SUBTYPE: u8 = 223;
+pub const BIT_VECTOR_SUBTYPE: u8 = 224;
+pub const INT8_VECTOR_SUBTYPE: u8 = 225;
+
+/// The element type of the vectors a client returns, matching the vector types of
+/// [`sqlite-vec`](https://github.com/asg017/sqlite-vec).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VectorType {
+    /// One little-endian 32-bit float per dimension.
+    #[default]
+    Float32,
+    /// One signed byte per dimension, with values between -128 and 127.
+    Int8,
+    /// One bit per dimension, packed eight to a byte, least significant bit first, into
+    /// bytes with values between 0 and 255.
+    Bit,
+}
+
+impl VectorType {
+    /// The subtype that `sqlite-vec` reads the BLOB's vector type from.
+    pub fn subtype(self) -> u8 {
+        match self {
+            VectorType::Float32 => FLOAT32_VECTOR_SUBTYPE,
+            VectorType::Int8 => INT8_VECTOR_SUBTYPE,
+            VectorType::Bit => BIT_VECTOR_SUBTYPE,
+        }
+    }
+
+    /// The vector type tagged with a `sqlite-vec` subtype.
+    pub fn from_subtype(subtype: u8) -> Option<VectorType> {
+        [VectorType::Float32, VectorType::Int8, VectorType::Bit]
+            .into_iter()
+            .find(|vector_type| vector_type.subtype() == subtype)
+    }
+
+    /// The vector type with the given [`name`](Self::name).
+    pub fn from_name(name: &str) -> Option<VectorType> {
+        [VectorType::Float32, VectorType::Int8, VectorType::Bit]
+            .into_iter()
+            .find(|vector_type| vector_type.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VectorType::Float32 => "float32",
+            VectorType::Int8 => "int8",
+            VectorType::Bit => "bit",
+        }
+    }
+
+    /// Quantizes a float embedding into this type's values, like `sqlite-vec`'s
+    /// `vec_quantize_int8()` and `vec_quantize_binary()`. Int8 values are scaled by 127,
+    /// which suits unit-length embeddings. Bits are set for positive values and packed
+    /// eight to a byte, least significant bit first.
+    pub fn quantize(self, embedding: &[f32]) -> Vec<f32> {
+        match self {
+            VectorType::Float32 => embedding.to_vec(),
+            VectorType::Int8 => embedding
+                .iter()
+                .map(|value| (value * 127.0).round().clamp(-128.0, 127.0))
+                .collect(),
+            VectorType::Bit => embedding
+                .chunks(8)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, value)| **value > 0.0)
+                        .fold(0u8, |byte, (i, _)| byte | (1 << i)) as f32
+                })
+                .collect(),
+        }
+    }
+
+    /// Encodes an embedding's values as a `sqlite-vec` BLOB of this type.
+    pub fn to_blob(self, embedding: &[f32]) -> Vec<u8> {
+        match self {
+            VectorType::Float32 => embedding.as_bytes().to_vec(),
+            VectorType::Int8 => embedding
+                .iter()
+                .map(|value| value.round().clamp(-128.0, 127.0) as i8 as u8)
+                .collect(),
+            VectorType::Bit => embedding
+                .iter()
+                .map(|value| value.round().clamp(0.0, 255.0) as u8)
+                .collect(),
+        }
+    }
+
+    /// Decodes a `sqlite-vec` BLOB of this type into a JSON array, like `sqlite-vec`'s
+    /// `vec_to_json()`. Bit vectors have a 0 or 1 per dimension.
+    pub fn blob_to_json(self, blob: &[u8]) -> Result<serde_json::Value> {
+        let values = match self {
+            VectorType::Float32 => {
+                if !blob.len().is_multiple_of(4) {
+                    return Err(Error::new_message(format!(
+                        "float32 vector BLOB must be a multiple of 4 bytes, found {} bytes",
+                        blob.len()
+                    )));
+                }
+                blob.chunks_exact(4)
+                    .map(|bytes| {
+                        let value = f32::from_le_bytes(bytes.try_into().unwrap());
+                        // f32's shortest representation, rather than the f64 it widens to
+                        value
+                            .to_string()
+                            .parse::<f64>()
+                            .ok()
+                            .and_then(serde_json::Number::from_f64)
+                            .map_or(serde_json::Value::Null, serde_json::Value::Number)
+                    })
+                    .collect()
+            }
+            VectorType::Int8 => blob.iter().map(|byte| (*byte as i8).into()).collect(),
+            VectorType::Bit => blob
+                .iter()
+                .flat_map(|byte| (0..8).map(move |i| ((byte >> i) & 1).into()))
+                .collect(),
+        };
+        Ok(serde_json::Value::Array(values))
+    }
+
+    /// Encodes a JSON array, as returned by [`blob_to_json`](Self::blob_to_json), as a
+    /// `sqlite-vec` BLOB of this type.
+    pub fn json_to_blob(self, json: &serde_json::Value) -> Result<Vec<u8>> {
+        let values = json
+            .as_array()
+            .ok_or_else(|| Error::new_message("expected a JSON array of numbers"))?
+            .iter()
+            .map(|value| {
+                value
+                    .as_f64()
+                    .ok_or_else(|| Error::new_message("expected a JSON array of numbers"))
+            })
+            .collect::<Result<Vec<f64>>>()?;
+        match self {
+            VectorType::Float32 => Ok(values
+                .iter()
+                .map(|value| *value as f32)
+                .collect::<Vec<f32>>()
+                .as_bytes()
+                .to_vec()),
+            VectorType::Int8 => values
+                .iter()
+                .map(|value| match *value {
+                    value if value.fract() == 0.0 && (-128.0..=127.0).contains(&value) => {
+                        Ok(value as i8 as u8)
+                    }
+                    value => Err(Error::new_message(format!(
+                        "int8 vector values must be integers between -128 and 127, found {value}"
+                    ))),
+                })
+                .collect(),
+            VectorType::Bit => {
+                if !values.len().is_multiple_of(8) {
+                    return Err(Error::new_message(format!(
+                        "bit vectors must have a multiple of 8 dimensions, found {}",
+                        values.len()
+                    )));
+                }
+                values
+                    .chunks(8)
+                    .map(|bits| {
+                        bits.iter()
+                            .enumerate()
+                            .try_fold(0u8, |byte, (i, bit)| match *bit {
+                                0.0 => Ok(byte),
+                                1.0 => Ok(byte | (1 << i)),
+                                bit => Err(Error::new_message(format!(
+                                    "bit vector values must be 0 or 1, found {bit}"
+                                ))),
+                            })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Scales `embedding` to unit length. Zero vectors are left as they are.
+pub fn normalize(embedding: &mut [f32]) {
+    let norm = embedding
+        .iter()
+        .map(|value| value * value)
+        .sum::<f32>()
+        .sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|value| *value /= norm);
+    }
+}
+
+/// Converts a provider's packed bit embedding into [`VectorType::Bit`] bytes. Providers pack
+/// bits most significant bit first, like numpy's `packbits()`, and their signed `binary`
+/// type offsets each byte by -128, while `sqlite-vec` packs least significant bit first.
+pub fn unpack_provider_bits(embedding: &mut [f32], signed: bool) {
+    let offset = if signed { 128.0 } else { 0.0 };
+    embedding.iter_mut().for_each(|value| {
+        let byte = (*value + offset).round().clamp(0.0, 255.0) as u8;
+        *value = byte.reverse_bits() as f32;
+    });
+}
@@ -5,6 +5,17 @@
 .timer on
 .echo on
 
+-- Regression check: UPDATE ... SET options = rembed_client_options(...) replaces the
+-- client. With .bail on, json() on a non-JSON message stops the script when it doesn't.
+INSERT INTO temp.rembed_clients(name, options) VALUES
+  ('rotated', rembed_client_options('format', 'openai', 'model', 'text-embedding-3-small', 'key', 'old'));
+UPDATE temp.rembed_clients
+  SET options = rembed_client_options('format', 'jina', 'model', 'jina-embeddings-v3', 'key', 'new')
+  WHERE name = 'rotated';
+SELECT iif(format = 'jina', 'ok', json('UPDATE did not replace the client')) AS updated
+  FROM temp.rembed_clients WHERE name = 'rotated';
+DELETE FROM temp.rembed_clients WHERE name = 'rotated';
+
 INSERT INTO temp.rembed_clients(name, options) VALUES
   ('text-embedding-3-small','openai'),
   ('jina-embeddings-v2-base-en','jina'),
@@ -17,8 +28,15 @@ INSERT INTO temp.rembed_clients(name, options) VALUES
     'mxbai-embed-large-v1-f16',
     rembed_client_options(
       'format', 'llamafile',
-      --'url', 'http://mm1:8080/v1/embeddings'
-      'url', 'http://mm1:8080/embedding'
+      'url', 'http://mm1:8080/embedding',
+      'batch_size', '32'
+    )
+  ),
+  (
+    'mxbai-embed-large-v1-f16-openai',
+    rembed_client_options(
+      'format', 'llamafile',
+      'url', 'http://mm1:8080/v1/embeddings'
     )
   );
 
@@ -33,5 +51,6 @@ select length(rembed('llamafile', 'obama the person'));
 select length(rembed('snowflake-arctic-embed:s', 'obama the person'));
 select length(rembed('embed-english-v3.0', 'obama the person', 'search_document'));
 select length(rembed('mxbai-embed-large-v1-f16', 'obama the person'));
+select length(rembed('mxbai-embed-large-v1-f16-openai', 'obama the person'));
 
 